@@ -0,0 +1,153 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use mem_access::{Memory, NaiveMemory, PagedMemory, TransactionId};
+
+type Paged = PagedMemory<256, 256>;
+type Naive = NaiveMemory<256, 256, 65536>;
+
+const SIZES: [usize; 4] = [1, 64, 4096, 65536];
+const DEPTHS: [usize; 3] = [10, 100, 1000];
+
+// Start in the middle of a page so that every multi-byte access crosses a page boundary.
+fn start_addr(size: usize) -> usize {
+    128.min(65536 - size)
+}
+
+fn filled<M: Memory>(mut memory: M) -> M {
+    for page in 0..256 {
+        memory
+            .add_transaction(page * 256, vec![page as u8; 256], 0x0)
+            .unwrap();
+    }
+    memory
+}
+
+fn with_history<M: Memory>(mut memory: M, depth: usize) -> M {
+    for i in 0..depth {
+        memory
+            .add_transaction((i * 97) % (65536 - 64), vec![i as u8; 64], 0x0)
+            .unwrap();
+    }
+    memory
+}
+
+fn read(c: &mut Criterion) {
+    let paged = filled(Paged::new(0xab));
+    let naive = filled(Naive::new(0xab));
+    let mut group = c.benchmark_group("read");
+    for size in SIZES {
+        let addr = start_addr(size);
+        group.bench_with_input(BenchmarkId::new("paged", size), &size, |b, &size| {
+            b.iter(|| paged.read(black_box(addr), black_box(size)))
+        });
+        group.bench_with_input(BenchmarkId::new("naive", size), &size, |b, &size| {
+            b.iter(|| naive.read(black_box(addr), black_box(size)))
+        });
+    }
+    group.finish();
+}
+
+fn add_transaction(c: &mut Criterion) {
+    let paged = Paged::new(0xab);
+    let naive = Naive::new(0xab);
+    let mut group = c.benchmark_group("add_transaction");
+    for size in SIZES {
+        let addr = start_addr(size);
+        group.bench_with_input(BenchmarkId::new("paged", size), &size, |b, &size| {
+            b.iter_batched_ref(
+                || paged.clone(),
+                |memory| memory.add_transaction(addr, vec![0x42; size], 0x0),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("naive", size), &size, |b, &size| {
+            b.iter_batched_ref(
+                || naive.clone(),
+                |memory| memory.add_transaction(addr, vec![0x42; size], 0x0),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn write_fill(c: &mut Criterion) {
+    let paged = Paged::new(0xab);
+    let naive = Naive::new(0xab);
+    let mut group = c.benchmark_group("write_fill");
+    group.bench_function("paged", |b| {
+        b.iter_batched_ref(
+            || paged.clone(),
+            |memory| memory.write_fill(0, 65536, 0x42, 0x0),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("naive", |b| {
+        b.iter_batched_ref(
+            || naive.clone(),
+            |memory| memory.write_fill(0, 65536, 0x42, 0x0),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn first_write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("first_write");
+    group.bench_function("lazy", |b| {
+        b.iter_batched_ref(
+            || Paged::new(0xab),
+            |memory| memory.write_fill(0, 65536, 0x42, 0x0),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("committed", |b| {
+        b.iter_batched_ref(
+            || Paged::commit_all_pages(0xab),
+            |memory| memory.write_fill(0, 65536, 0x42, 0x0),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn move_to_transaction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("move_to_transaction");
+    for depth in DEPTHS {
+        let mut paged = with_history(Paged::new(0xab), depth);
+        let mut naive = with_history(Naive::new(0xab), depth);
+        let last = paged.checkpoint();
+        group.bench_with_input(BenchmarkId::new("paged", depth), &depth, |b, _| {
+            b.iter(|| {
+                paged.move_to_transaction(TransactionId::INITIAL).unwrap();
+                paged.restore(last).unwrap();
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("naive", depth), &depth, |b, _| {
+            b.iter(|| {
+                naive.move_to_transaction(TransactionId::INITIAL).unwrap();
+                naive.restore(last).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+fn clone(c: &mut Criterion) {
+    let paged = filled(Paged::new(0xab));
+    let naive = filled(Naive::new(0xab));
+    let mut group = c.benchmark_group("clone");
+    group.bench_function("paged", |b| b.iter(|| paged.clone()));
+    group.bench_function("naive", |b| b.iter(|| naive.clone()));
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    read,
+    add_transaction,
+    write_fill,
+    first_write,
+    move_to_transaction,
+    clone
+);
+criterion_main!(benches);
@@ -29,7 +29,7 @@ fuzz_target!(|fuzz_data: FuzzData| {
 
     for write in fuzz_data.writes {
         let (max_addr, overflowed) = write.addr.overflowing_add(write.data.len());
-        if overflowed || max_addr >= 256 * 256 {
+        if overflowed || max_addr > 256 * 256 {
             continue;
         } else if write.data.is_empty() {
             continue;
@@ -43,7 +43,7 @@ fuzz_target!(|fuzz_data: FuzzData| {
     }
     for read in fuzz_data.reads {
         let (max_addr, overflowed) = read.addr.overflowing_add(read.size);
-        if overflowed || max_addr >= 256 * 256 {
+        if overflowed || max_addr > 256 * 256 {
             continue;
         } else if read.size == 0 {
             continue;
@@ -51,4 +51,5 @@ fuzz_target!(|fuzz_data: FuzzData| {
 
         assert_eq!(paged_memory.read(read.addr, read.size), naive_memory.read(read.addr, read.size));
     }
+    assert!(paged_memory.memory_eq(&naive_memory));
 });
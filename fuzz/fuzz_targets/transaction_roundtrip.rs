@@ -0,0 +1,38 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use pretty_assertions::assert_eq;
+
+use mem_access::{Memory, PagedMemory, Transaction, TransactionId};
+
+#[derive(Arbitrary, Debug)]
+struct FuzzData {
+    writes: Vec<(u16, Vec<u8>)>,
+    annotations: Vec<(String, String)>,
+    raw: Vec<u8>,
+}
+
+fn assert_roundtrip(transaction: &Transaction) {
+    let decoded = Transaction::try_from(transaction.to_bytes().as_slice()).unwrap();
+    assert_eq!(&decoded, transaction);
+    assert_eq!(decoded.annotations(), transaction.annotations());
+}
+
+fuzz_target!(|fuzz_data: FuzzData| {
+    let mut paged_memory = PagedMemory::<256, 256>::new(0xab);
+    for (addr, data) in fuzz_data.writes {
+        let _ = paged_memory.add_transaction(addr.into(), data, 0);
+    }
+    let current = TransactionId::try_from(paged_memory.current_transaction_id()).unwrap();
+    for (key, value) in &fuzz_data.annotations {
+        let _ = paged_memory.annotate(current, key, value);
+    }
+    if let Some(transaction) = paged_memory.current_transaction() {
+        assert_roundtrip(&transaction);
+    }
+
+    if let Ok(transaction) = Transaction::try_from(fuzz_data.raw.as_slice()) {
+        assert_roundtrip(&transaction);
+    }
+});
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use crate::{Memory, MemoryError, PagedMemory, TransactionId};
+
+/// Several named memories that share one transaction history.
+///
+/// Every write through the arena advances a global transaction counter, and navigation
+/// moves all members to the matching point in that history.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryArena<const NUM_PAGES: usize, const PAGE_SIZE: usize> {
+    memories: HashMap<String, Member<NUM_PAGES, PAGE_SIZE>>,
+    /// Name of the memory written by each global transaction.
+    log: Vec<String>,
+    transaction_idx: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Member<const NUM_PAGES: usize, const PAGE_SIZE: usize> {
+    memory: PagedMemory<NUM_PAGES, PAGE_SIZE>,
+    /// Global id of every transaction in the member's own log, in order.
+    global_ids: Vec<TransactionId>,
+}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> MemoryArena<NUM_PAGES, PAGE_SIZE> {
+    pub fn new() -> Self {
+        Self {
+            memories: HashMap::new(),
+            log: Vec::new(),
+            transaction_idx: 0,
+        }
+    }
+
+    /// Adds an empty memory called `name`.
+    pub fn add_memory(&mut self, name: &str, default_value: u8) -> Result<(), MemoryError> {
+        if self.memories.contains_key(name) {
+            return Err(MemoryError::InvalidParams(format!(
+                "memory {name:?} already exists"
+            )));
+        }
+        let member = Member {
+            memory: PagedMemory::new(default_value),
+            global_ids: Vec::new(),
+        };
+        self.memories.insert(name.to_string(), member);
+        Ok(())
+    }
+
+    /// The member memory called `name`. Transaction ids read from it directly count only
+    /// that member's writes; [`MemoryArena::read_transaction_ids`] reports arena-wide ids.
+    pub fn memory(&self, name: &str) -> Option<&PagedMemory<NUM_PAGES, PAGE_SIZE>> {
+        self.memories.get(name).map(|member| &member.memory)
+    }
+
+    /// Arena-wide ids of the transactions that last wrote `addr..addr + size` of memory `name`.
+    pub fn read_transaction_ids(
+        &self,
+        name: &str,
+        addr: usize,
+        size: usize,
+    ) -> Option<Vec<TransactionId>> {
+        let member = self.memories.get(name)?;
+        let ids = member
+            .memory
+            .read_transaction_ids(addr, size)
+            .into_iter()
+            .map(|id| match (id.0 as usize).checked_sub(1) {
+                Some(idx) => member.global_ids[idx],
+                None => id,
+            })
+            .collect();
+        Some(ids)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.memories.keys().map(String::as_str)
+    }
+
+    pub fn current_transaction_id(&self) -> TransactionId {
+        TransactionId(self.transaction_idx as u32)
+    }
+
+    /// Name of the memory written by the transaction that produced state `id`.
+    pub fn transaction_memory(&self, id: TransactionId) -> Option<&str> {
+        let idx = (id.0 as usize).checked_sub(1)?;
+        self.log.get(idx).map(String::as_str)
+    }
+
+    pub fn add_transaction(
+        &mut self,
+        name: &str,
+        addr: usize,
+        data: Vec<u8>,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        if self.transaction_idx != self.log.len() {
+            return Err(MemoryError::NotAtLatestTransaction);
+        }
+        let member = self
+            .memories
+            .get_mut(name)
+            .ok_or_else(|| MemoryError::InvalidParams(format!("no memory named {name:?}")))?;
+        member.memory.add_transaction(addr, data, code_location)?;
+        member
+            .global_ids
+            .push(TransactionId(self.transaction_idx as u32 + 1));
+        self.log.push(name.to_string());
+        self.transaction_idx += 1;
+        Ok(())
+    }
+
+    pub fn move_to_transaction(&mut self, id: TransactionId) -> Result<(), MemoryError> {
+        let target_idx = id.0 as usize;
+        if target_idx > self.log.len() {
+            return Err(MemoryError::InvalidTransactionId(id));
+        }
+        while self.transaction_idx < target_idx {
+            self.member_of(self.transaction_idx).next_transaction()?;
+            self.transaction_idx += 1;
+        }
+        while self.transaction_idx > target_idx {
+            self.member_of(self.transaction_idx - 1)
+                .previous_transaction()?;
+            self.transaction_idx -= 1;
+        }
+        Ok(())
+    }
+
+    fn member_of(&mut self, idx: usize) -> &mut PagedMemory<NUM_PAGES, PAGE_SIZE> {
+        &mut self
+            .memories
+            .get_mut(&self.log[idx])
+            .expect("every logged transaction belongs to a member memory")
+            .memory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn shared_history() {
+        let mut arena = MemoryArena::<4, 4>::new();
+        arena.add_memory("ram", 0x00).unwrap();
+        arena.add_memory("vram", 0xff).unwrap();
+        assert_eq!(
+            arena.add_memory("ram", 0x00),
+            Err(MemoryError::InvalidParams(
+                "memory \"ram\" already exists".to_string()
+            ))
+        );
+
+        arena.add_transaction("ram", 0x0, vec![1, 2], 0x0).unwrap();
+        arena.add_transaction("vram", 0x4, vec![3], 0x0).unwrap();
+        arena.add_transaction("ram", 0x1, vec![4], 0x0).unwrap();
+        assert_eq!(arena.current_transaction_id(), TransactionId(3));
+        assert_eq!(arena.transaction_memory(TransactionId(2)), Some("vram"));
+        assert_eq!(arena.transaction_memory(TransactionId(0)), None);
+        assert_eq!(
+            arena.add_transaction("rom", 0x0, vec![1], 0x0),
+            Err(MemoryError::InvalidParams(
+                "no memory named \"rom\"".to_string()
+            ))
+        );
+
+        arena.move_to_transaction(TransactionId(1)).unwrap();
+        assert_eq!(arena.memory("ram").unwrap().read(0x0, 2), vec![1, 2]);
+        assert_eq!(arena.memory("vram").unwrap().read(0x4, 1), vec![0xff]);
+        assert_eq!(
+            arena.add_transaction("ram", 0x0, vec![5], 0x0),
+            Err(MemoryError::NotAtLatestTransaction)
+        );
+
+        arena.move_to_transaction(TransactionId(3)).unwrap();
+        assert_eq!(arena.memory("ram").unwrap().read(0x0, 2), vec![1, 4]);
+        assert_eq!(arena.memory("vram").unwrap().read(0x4, 1), vec![3]);
+        assert_eq!(
+            arena.read_transaction_ids("ram", 0x0, 2),
+            Some(vec![TransactionId(1), TransactionId(3)])
+        );
+        assert_eq!(
+            arena.read_transaction_ids("vram", 0x3, 2),
+            Some(vec![TransactionId(0), TransactionId(2)])
+        );
+        assert_eq!(arena.read_transaction_ids("rom", 0x0, 1), None);
+        assert_eq!(
+            arena.memory("ram").unwrap().read_transaction_ids(0x0, 2),
+            vec![TransactionId(1), TransactionId(2)]
+        );
+
+        arena.move_to_transaction(TransactionId(0)).unwrap();
+        assert_eq!(arena.memory("ram").unwrap().read(0x0, 2), vec![0, 0]);
+        assert_eq!(
+            arena.read_transaction_ids("ram", 0x0, 2),
+            Some(vec![TransactionId(0); 2])
+        );
+        assert_eq!(
+            arena.move_to_transaction(TransactionId(4)),
+            Err(MemoryError::InvalidTransactionId(TransactionId(4)))
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use crate::internal_memory_ops::{InternalMemoryOps, Transaction, TransactionData};
+use crate::{address_chunks, Memory, MemoryError, TransactionId, TransactionStorage};
+
+/// Storage for the bytes of a [`BackendMemory`], such as shared memory, a memory-mapped file
+/// or a remote debugger stub.
+///
+/// A backend stores each byte together with the id of the transaction that last wrote it.
+/// The transaction log and the position in history belong to [`BackendMemory`], which is the
+/// only caller of the mutating methods. All methods are required, and every range passed to
+/// them is non-empty and lies inside the address space.
+///
+/// - A byte that was never written reads as [`MemoryBackend::default_value`] with id
+///   [`TransactionId::INITIAL`].
+/// - A byte holding content that no logged transaction wrote, such as an image loaded into
+///   the backend up front, also has id `INITIAL` but is *initialized*. It keeps its value
+///   when the default value changes.
+/// - `write_data` and `write_transaction_ids` store exactly what they are given and must
+///   not change which bytes are initialized.
+///
+/// ```
+/// use mem_access::{BackendMemory, Memory, MemoryBackend, TransactionId};
+///
+/// struct VecBackend {
+///     default_value: u8,
+///     data: Vec<u8>,
+///     ids: Vec<TransactionId>,
+///     initialized: Vec<bool>,
+/// }
+///
+/// impl MemoryBackend for VecBackend {
+///     fn address_space_size(&self) -> usize {
+///         self.data.len()
+///     }
+///     fn default_value(&self) -> u8 {
+///         self.default_value
+///     }
+///     fn read(&self, addr: usize, size: usize) -> Vec<u8> {
+///         self.data[addr..addr + size].to_vec()
+///     }
+///     fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId> {
+///         self.ids[addr..addr + size].to_vec()
+///     }
+///     fn write_data(&mut self, addr: usize, data: &[u8]) {
+///         self.data[addr..addr + data.len()].copy_from_slice(data);
+///     }
+///     fn write_transaction_ids(&mut self, addr: usize, ids: &[TransactionId]) {
+///         self.ids[addr..addr + ids.len()].copy_from_slice(ids);
+///     }
+///     fn is_initialized(&self, addr: usize) -> bool {
+///         self.initialized[addr]
+///     }
+///     fn mark_initialized(&mut self, addr: usize, size: usize) {
+///         self.initialized[addr..addr + size].fill(true);
+///     }
+///     fn replace_default_value(&mut self, new_default: u8) {
+///         for (addr, byte) in self.data.iter_mut().enumerate() {
+///             if self.ids[addr] == TransactionId::INITIAL && !self.initialized[addr] {
+///                 *byte = new_default;
+///             }
+///         }
+///         self.default_value = new_default;
+///     }
+/// }
+///
+/// let mut memory = BackendMemory::new(VecBackend {
+///     default_value: 0,
+///     data: vec![0; 16],
+///     ids: vec![TransactionId::INITIAL; 16],
+///     initialized: vec![false; 16],
+/// });
+/// memory.add_transaction(0x4, vec![1, 2], 0x0).unwrap();
+/// memory.previous_transaction().unwrap();
+/// assert_eq!(memory.read(0x4, 2), vec![0, 0]);
+/// ```
+pub trait MemoryBackend {
+    fn address_space_size(&self) -> usize;
+    fn default_value(&self) -> u8;
+    fn read(&self, addr: usize, size: usize) -> Vec<u8>;
+    fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId>;
+    fn write_data(&mut self, addr: usize, data: &[u8]);
+    fn write_transaction_ids(&mut self, addr: usize, transaction_ids: &[TransactionId]);
+    /// Whether the byte at `addr` is initialized. Backends may track this more coarsely, for
+    /// example per page; bytes reported as initialized by mistake are only left out of rebases.
+    fn is_initialized(&self, addr: usize) -> bool;
+    /// Marks `addr..addr + size` as initialized. Called when the transactions that wrote the
+    /// range are dropped from the log.
+    fn mark_initialized(&mut self, addr: usize, size: usize);
+    /// Changes the default value. Bytes with id [`TransactionId::INITIAL`] that are not
+    /// initialized read as `new_default` afterwards.
+    fn replace_default_value(&mut self, new_default: u8);
+}
+
+/// [`Memory`] that keeps its transaction log in process and its contents in a
+/// [`MemoryBackend`].
+///
+/// Clearing, truncating and compacting the log rewrite the transaction ids of the whole
+/// address space, one chunk at a time.
+///
+/// Log entries are [`Transaction`]s unless another [`TransactionStorage`] is chosen with
+/// [`BackendMemory::with_storage`], e.g. [`crate::SmallTransaction`] to keep them inline.
+#[derive(Debug, Clone, Default)]
+pub struct BackendMemory<B: MemoryBackend, S: TransactionStorage = Transaction> {
+    backend: B,
+    #[cfg(feature = "protection")]
+    protection: crate::protection::ProtectionMap,
+    transactions: Vec<S>,
+    transaction_idx: usize,
+}
+
+impl<B: MemoryBackend> BackendMemory<B> {
+    /// Wraps `backend` with an empty transaction log. Its current contents become the
+    /// initial state.
+    pub fn new(backend: B) -> Self {
+        Self::with_storage(backend)
+    }
+}
+
+impl<B: MemoryBackend, S: TransactionStorage> BackendMemory<B, S> {
+    /// Same as [`BackendMemory::new`], with log entries stored as `S`.
+    pub fn with_storage(backend: B) -> Self {
+        Self {
+            backend,
+            #[cfg(feature = "protection")]
+            protection: Default::default(),
+            transactions: Vec::new(),
+            transaction_idx: 0,
+        }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Returns the backend at the current position in history, dropping the log.
+    pub fn into_backend(self) -> B {
+        self.backend
+    }
+
+    fn assert_in_range(&self, addr: usize, size: usize) {
+        assert!(size > 0);
+        assert!(addr
+            .checked_add(size)
+            .is_some_and(|end| end <= self.backend.address_space_size()));
+    }
+}
+
+impl<B: MemoryBackend, S: TransactionStorage> InternalMemoryOps for BackendMemory<B, S> {
+    type TransactionStorage = S;
+
+    fn transactions(&self) -> &[S] {
+        &self.transactions
+    }
+
+    fn transactions_mut(&mut self) -> &mut Vec<S> {
+        &mut self.transactions
+    }
+
+    fn transaction_vec_len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    fn transaction_vec_capacity(&self) -> usize {
+        self.transactions.capacity()
+    }
+
+    fn transaction_vec_push(&mut self, transaction: S) {
+        self.transactions.push(transaction);
+    }
+
+    fn get_mut_transaction(&mut self, idx: usize) -> Option<&mut S> {
+        self.transactions.get_mut(idx)
+    }
+
+    fn set_transaction_idx(&mut self, idx: usize) {
+        self.transaction_idx = idx;
+    }
+
+    fn write_data(&mut self, addr: usize, data: &[u8]) {
+        self.backend.write_data(addr, data);
+    }
+
+    fn write_transaction_ids(&mut self, addr: usize, transaction_ids: &[TransactionId]) {
+        self.backend.write_transaction_ids(addr, transaction_ids);
+    }
+
+    fn clear_transaction_ids(&mut self) {
+        self.remap_transaction_ids(&|_| TransactionId::INITIAL);
+    }
+
+    fn remap_transaction_ids(&mut self, map: &dyn Fn(TransactionId) -> TransactionId) {
+        for (addr, size) in address_chunks(0, self.backend.address_space_size()) {
+            let ids = self.backend.read_transaction_ids(addr, size);
+            let new_ids: Vec<_> = ids.iter().map(|&id| map(id)).collect();
+            if new_ids == ids {
+                continue;
+            }
+            for (offset, (old_id, new_id)) in ids.iter().zip(new_ids.iter()).enumerate() {
+                if *old_id != TransactionId::INITIAL && *new_id == TransactionId::INITIAL {
+                    self.backend.mark_initialized(addr + offset, 1);
+                }
+            }
+            self.backend.write_transaction_ids(addr, &new_ids);
+        }
+    }
+
+    fn default_value(&self) -> u8 {
+        self.backend.default_value()
+    }
+
+    fn is_initialized(&self, addr: usize) -> bool {
+        self.backend.is_initialized(addr)
+    }
+
+    fn replace_default_value(&mut self, new_default: u8) {
+        self.backend.replace_default_value(new_default);
+    }
+
+    fn add_transaction_data(
+        &mut self,
+        addr: usize,
+        data: TransactionData,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        crate::record_transaction(self, addr, data, code_location)
+    }
+
+    #[cfg(feature = "protection")]
+    fn protection(&self) -> &crate::protection::ProtectionMap {
+        &self.protection
+    }
+
+    #[cfg(feature = "protection")]
+    fn protection_mut(&mut self) -> &mut crate::protection::ProtectionMap {
+        &mut self.protection
+    }
+}
+
+impl<B: MemoryBackend, S: TransactionStorage> Memory for BackendMemory<B, S> {
+    fn read(&self, addr: usize, size: usize) -> Vec<u8> {
+        self.assert_in_range(addr, size);
+        self.backend.read(addr, size)
+    }
+
+    fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId> {
+        self.assert_in_range(addr, size);
+        self.backend.read_transaction_ids(addr, size)
+    }
+
+    fn current_transaction_id(&self) -> usize {
+        self.transaction_idx
+    }
+
+    fn address_space_size(&self) -> usize {
+        self.backend.address_space_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[derive(Debug)]
+    struct VecBackend {
+        default_value: u8,
+        data: Vec<u8>,
+        ids: Vec<TransactionId>,
+        initialized: Vec<bool>,
+    }
+
+    impl VecBackend {
+        fn new(size: usize, default_value: u8) -> Self {
+            Self {
+                default_value,
+                data: vec![default_value; size],
+                ids: vec![TransactionId::INITIAL; size],
+                initialized: vec![false; size],
+            }
+        }
+    }
+
+    impl MemoryBackend for VecBackend {
+        fn address_space_size(&self) -> usize {
+            self.data.len()
+        }
+
+        fn default_value(&self) -> u8 {
+            self.default_value
+        }
+
+        fn read(&self, addr: usize, size: usize) -> Vec<u8> {
+            self.data[addr..addr + size].to_vec()
+        }
+
+        fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId> {
+            self.ids[addr..addr + size].to_vec()
+        }
+
+        fn write_data(&mut self, addr: usize, data: &[u8]) {
+            self.data[addr..addr + data.len()].copy_from_slice(data);
+        }
+
+        fn write_transaction_ids(&mut self, addr: usize, transaction_ids: &[TransactionId]) {
+            self.ids[addr..addr + transaction_ids.len()].copy_from_slice(transaction_ids);
+        }
+
+        fn is_initialized(&self, addr: usize) -> bool {
+            self.initialized[addr]
+        }
+
+        fn mark_initialized(&mut self, addr: usize, size: usize) {
+            self.initialized[addr..addr + size].fill(true);
+        }
+
+        fn replace_default_value(&mut self, new_default: u8) {
+            for (addr, byte) in self.data.iter_mut().enumerate() {
+                if self.ids[addr] == TransactionId::INITIAL && !self.initialized[addr] {
+                    *byte = new_default;
+                }
+            }
+            self.default_value = new_default;
+        }
+    }
+
+    #[test]
+    fn truncate_and_rebase() {
+        let mut memory = BackendMemory::new(VecBackend::new(16, 0xab));
+        memory.add_transaction(0x2, vec![1, 2], 0x0).unwrap();
+        memory.add_transaction(0x3, vec![0xab, 3], 0x0).unwrap();
+        memory.add_transaction(0x8, vec![4], 0x0).unwrap();
+        memory.truncate_transaction_log(2);
+        assert_eq!(
+            memory.read_transaction_ids(0x2, 3),
+            vec![TransactionId(0), TransactionId(1), TransactionId(1)]
+        );
+        assert!(memory.backend().is_initialized(0x2));
+        assert!(!memory.backend().is_initialized(0x3));
+
+        memory.rebase(0x00);
+        let mut expected = vec![0x00; 16];
+        expected[0x2..0x5].copy_from_slice(&[1, 0xab, 3]);
+        expected[0x8] = 4;
+        assert_eq!(memory.read(0x0, 16), expected);
+
+        memory.move_to_transaction(TransactionId(0)).unwrap();
+        expected[0x3..0x5].copy_from_slice(&[2, 0x00]);
+        expected[0x8] = 0x00;
+        assert_eq!(memory.read(0x0, 16), expected);
+        assert_eq!(memory.validate_integrity(), Ok(()));
+        assert_eq!(memory.into_backend().data, expected);
+    }
+}
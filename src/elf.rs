@@ -0,0 +1,129 @@
+use crate::MemoryError;
+
+const PT_LOAD: u32 = 1;
+
+/// A `PT_LOAD` segment: `data` goes to `addr`, the remaining `mem_size - data.len()` bytes
+/// are zero-filled.
+pub(crate) struct LoadSegment<'a> {
+    pub addr: usize,
+    pub data: &'a [u8],
+    pub mem_size: usize,
+}
+
+struct Reader<'a> {
+    image: &'a [u8],
+    is_64: bool,
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N], MemoryError> {
+        offset
+            .checked_add(N)
+            .and_then(|end| self.image.get(offset..end))
+            .map(|bytes| bytes.try_into().unwrap())
+            .ok_or(MemoryError::InvalidElf("image is truncated"))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, MemoryError> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, MemoryError> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn u64(&self, offset: usize) -> Result<u64, MemoryError> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        })
+    }
+
+    // Address-sized field: 4 bytes in ELF32, 8 bytes in ELF64.
+    fn word(&self, offset: usize) -> Result<usize, MemoryError> {
+        let value = if self.is_64 {
+            self.u64(offset)?
+        } else {
+            self.u32(offset)?.into()
+        };
+        usize::try_from(value).map_err(|_| MemoryError::InvalidElf("value does not fit usize"))
+    }
+}
+
+/// Parses the `PT_LOAD` program headers of an ELF32 or ELF64 image of either endianness.
+/// Segments are placed at their physical address.
+pub(crate) fn load_segments(image: &[u8]) -> Result<Vec<LoadSegment<'_>>, MemoryError> {
+    if image.get(..4) != Some(b"\x7fELF") {
+        return Err(MemoryError::InvalidElf("missing ELF magic"));
+    }
+    let is_64 = match image.get(4) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err(MemoryError::InvalidElf("unknown ELF class")),
+    };
+    let big_endian = match image.get(5) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err(MemoryError::InvalidElf("unknown data encoding")),
+    };
+    let reader = Reader {
+        image,
+        is_64,
+        big_endian,
+    };
+
+    let (ph_offset, ph_entry_size, ph_count) = if is_64 {
+        (reader.word(0x20)?, reader.u16(0x36)?, reader.u16(0x38)?)
+    } else {
+        (reader.word(0x1c)?, reader.u16(0x2a)?, reader.u16(0x2c)?)
+    };
+    // Offsets of p_offset, p_paddr, p_filesz and p_memsz within a program header.
+    let (offset_field, paddr_field, filesz_field, memsz_field) = if is_64 {
+        (0x08, 0x18, 0x20, 0x28)
+    } else {
+        (0x04, 0x0c, 0x10, 0x14)
+    };
+
+    let mut segments = Vec::new();
+    for idx in 0..usize::from(ph_count) {
+        let header = idx
+            .checked_mul(ph_entry_size.into())
+            .and_then(|offset| offset.checked_add(ph_offset))
+            .ok_or(MemoryError::InvalidElf("program header offset overflows"))?;
+        if reader.u32(header)? != PT_LOAD {
+            continue;
+        }
+        let file_offset = reader.word(header + offset_field)?;
+        let addr = reader.word(header + paddr_field)?;
+        let file_size = reader.word(header + filesz_field)?;
+        let mem_size = reader.word(header + memsz_field)?;
+        if file_size > mem_size {
+            return Err(MemoryError::InvalidElf(
+                "segment file size exceeds memory size",
+            ));
+        }
+        let data = file_offset
+            .checked_add(file_size)
+            .and_then(|end| image.get(file_offset..end))
+            .ok_or(MemoryError::InvalidElf("segment data is out of bounds"))?;
+        segments.push(LoadSegment {
+            addr,
+            data,
+            mem_size,
+        });
+    }
+    Ok(segments)
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+use std::io;
+
+use crate::TransactionId;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MemoryError {
+    AddressOutOfRange,
+    AddressOverflow {
+        base: usize,
+        offset: usize,
+    },
+    NoNextTransaction,
+    NoPreviousTransaction,
+    InvalidTransactionId(TransactionId),
+    NotAtLatestTransaction,
+    ConflictingTransaction(TransactionId),
+    PageOutOfRange(usize),
+    BufferTooSmall {
+        required: usize,
+        actual: usize,
+    },
+    SizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    OverlappingLoad {
+        first: usize,
+        second: usize,
+    },
+    EmptyTransaction,
+    TransactionTooLarge {
+        size: usize,
+        max_size: usize,
+    },
+    AnnotationsUnsupported,
+    NullTerminatorNotFound,
+    InvalidUtf8,
+    ReplayFailed {
+        index: usize,
+        source: Box<MemoryError>,
+    },
+    #[cfg(feature = "protection")]
+    WriteProtected(usize),
+    Io(io::ErrorKind),
+    InvalidParams(String),
+    #[cfg(feature = "elf")]
+    InvalidElf(&'static str),
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AddressOutOfRange => write!(f, "address range exceeds the address space"),
+            Self::AddressOverflow { base, offset } => {
+                write!(f, "{base:#x} + {offset:#x} overflows the address type")
+            }
+            Self::NoNextTransaction => write!(f, "there is no transaction to apply"),
+            Self::NoPreviousTransaction => write!(f, "there is no transaction to revert"),
+            Self::InvalidTransactionId(id) => write!(f, "transaction {id} does not exist"),
+            Self::NotAtLatestTransaction => {
+                write!(f, "memory is not positioned at the latest transaction")
+            }
+            Self::ConflictingTransaction(id) => {
+                write!(f, "transaction {id} overwrites the same addresses")
+            }
+            Self::PageOutOfRange(page_idx) => write!(f, "page {page_idx} does not exist"),
+            Self::BufferTooSmall { required, actual } => write!(
+                f,
+                "buffer of {actual} bytes is smaller than the required {required} bytes"
+            ),
+            Self::SizeMismatch { expected, actual } => {
+                write!(f, "expected {expected} bytes, got {actual}")
+            }
+            Self::OverlappingLoad { first, second } => {
+                write!(f, "load at {second:#x} overlaps the load at {first:#x}")
+            }
+            Self::EmptyTransaction => write!(f, "transaction does not write any bytes"),
+            Self::TransactionTooLarge { size, max_size } => write!(
+                f,
+                "transaction of {size} bytes exceeds the {max_size} bytes the log can store"
+            ),
+            Self::AnnotationsUnsupported => {
+                write!(f, "the transaction log does not store annotations")
+            }
+            Self::NullTerminatorNotFound => write!(f, "no null terminator in the given range"),
+            Self::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            Self::ReplayFailed { index, source } => {
+                write!(f, "replaying transaction {index} failed: {source}")
+            }
+            #[cfg(feature = "protection")]
+            Self::WriteProtected(addr) => write!(f, "address {addr:#x} is read-only"),
+            Self::Io(kind) => write!(f, "i/o error: {kind}"),
+            Self::InvalidParams(message) => write!(f, "invalid parameters: {message}"),
+            #[cfg(feature = "elf")]
+            Self::InvalidElf(reason) => write!(f, "invalid ELF image: {reason}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum IntegrityError {
+    TransactionIdxOutOfRange {
+        current: usize,
+        len: usize,
+    },
+    FutureTransactionId {
+        addr: usize,
+        id: TransactionId,
+    },
+    LengthMismatch {
+        index: usize,
+        data: usize,
+        old_data: usize,
+        old_ids: usize,
+    },
+    DanglingOldId {
+        index: usize,
+        addr: usize,
+        id: TransactionId,
+    },
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TransactionIdxOutOfRange { current, len } => write!(
+                f,
+                "current transaction {current} is beyond the end of the log ({len} transactions)"
+            ),
+            Self::FutureTransactionId { addr, id } => write!(
+                f,
+                "address {addr:#x} holds {id:?}, which is newer than the current transaction"
+            ),
+            Self::LengthMismatch {
+                index,
+                data,
+                old_data,
+                old_ids,
+            } => write!(
+                f,
+                "transaction {index} has {data} data bytes, {old_data} old data bytes and {old_ids} old ids"
+            ),
+            Self::DanglingOldId { index, addr, id } => write!(
+                f,
+                "transaction {index} restores {id:?} at address {addr:#x}, which does not precede it"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
+impl std::error::Error for MemoryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ReplayFailed { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::VecDeque;
+
+use crate::internal_memory_ops::{
+    delegate_internal_memory_ops, InternalMemoryOps, TransactionData,
+};
+use crate::{Memory, MemoryError, TransactionId};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MemoryEvent {
+    Write {
+        id: TransactionId,
+        addr: usize,
+        data: Vec<u8>,
+        code_location: usize,
+    },
+    Undo {
+        id: TransactionId,
+    },
+    Redo {
+        id: TransactionId,
+    },
+    /// The default value was replaced by [`Memory::rebase`].
+    Rebase {
+        default_value: u8,
+    },
+    /// The transaction log was truncated or compacted without changing memory contents. Ids
+    /// from earlier events may no longer name the same transactions; `id` is the current one.
+    HistoryRewritten {
+        id: TransactionId,
+    },
+}
+
+pub trait MemoryEventSource {
+    fn push_event(&mut self, event: MemoryEvent);
+    fn next_event(&mut self) -> Option<MemoryEvent>;
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct EventQueue {
+    events: VecDeque<MemoryEvent>,
+}
+
+impl EventQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MemoryEventSource for EventQueue {
+    fn push_event(&mut self, event: MemoryEvent) {
+        self.events.push_back(event);
+    }
+
+    fn next_event(&mut self) -> Option<MemoryEvent> {
+        self.events.pop_front()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StreamingMemory<M, E> {
+    memory: M,
+    source: E,
+}
+
+impl<M: Memory, E: MemoryEventSource> StreamingMemory<M, E> {
+    pub fn new(memory: M, source: E) -> Self {
+        Self { memory, source }
+    }
+
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    pub fn into_inner(self) -> (M, E) {
+        (self.memory, self.source)
+    }
+
+    pub fn next_event(&mut self) -> Option<MemoryEvent> {
+        self.source.next_event()
+    }
+
+    fn push_history_rewritten(&mut self) {
+        let id = TransactionId(self.memory.current_transaction_id() as u32);
+        self.source.push_event(MemoryEvent::HistoryRewritten { id });
+    }
+}
+
+impl<M: Memory, E: MemoryEventSource> InternalMemoryOps for StreamingMemory<M, E> {
+    delegate_internal_memory_ops!(memory: M);
+
+    fn add_transaction_data(
+        &mut self,
+        addr: usize,
+        data: TransactionData,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        self.memory
+            .add_transaction_data(addr, data, code_location)?;
+        let id = TransactionId(self.memory.current_transaction_id() as u32);
+        let transaction = self.memory.current_transaction().unwrap();
+        self.source.push_event(MemoryEvent::Write {
+            id,
+            addr,
+            data: transaction.new_data().into_owned(),
+            code_location,
+        });
+        Ok(())
+    }
+}
+
+impl<M: Memory, E: MemoryEventSource> Memory for StreamingMemory<M, E> {
+    fn read(&self, addr: usize, size: usize) -> Vec<u8> {
+        self.memory.read(addr, size)
+    }
+
+    fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId> {
+        self.memory.read_transaction_ids(addr, size)
+    }
+
+    fn current_transaction_id(&self) -> usize {
+        self.memory.current_transaction_id()
+    }
+
+    fn address_space_size(&self) -> usize {
+        self.memory.address_space_size()
+    }
+
+    fn next_transaction(&mut self) -> Result<(), MemoryError> {
+        self.memory.next_transaction()?;
+        let id = TransactionId(self.memory.current_transaction_id() as u32);
+        self.source.push_event(MemoryEvent::Redo { id });
+        Ok(())
+    }
+
+    fn previous_transaction(&mut self) -> Result<(), MemoryError> {
+        let id = TransactionId(self.memory.current_transaction_id() as u32);
+        self.memory.previous_transaction()?;
+        self.source.push_event(MemoryEvent::Undo { id });
+        Ok(())
+    }
+
+    fn rebase(&mut self, new_default: u8) {
+        if self.memory.default_value() == new_default {
+            return;
+        }
+        self.memory.rebase(new_default);
+        self.source.push_event(MemoryEvent::Rebase {
+            default_value: new_default,
+        });
+    }
+
+    fn clear_transaction_log_preserving_state(&mut self) {
+        self.memory.clear_transaction_log_preserving_state();
+        self.push_history_rewritten();
+    }
+
+    fn truncate_transaction_log(&mut self, max_entries: usize) {
+        if self.memory.transaction_vec_len() <= max_entries {
+            return;
+        }
+        self.memory.truncate_transaction_log(max_entries);
+        self.push_history_rewritten();
+    }
+
+    fn truncate_future(&mut self, current: TransactionId) -> Result<(), MemoryError> {
+        let len = self.memory.transaction_vec_len();
+        self.memory.truncate_future(current)?;
+        if self.memory.transaction_vec_len() != len {
+            self.push_history_rewritten();
+        }
+        Ok(())
+    }
+
+    fn clear_future_transactions(&mut self) -> usize {
+        let removed = self.memory.clear_future_transactions();
+        if removed > 0 {
+            self.push_history_rewritten();
+        }
+        removed
+    }
+
+    fn compact_adjacent_transactions(&mut self) -> usize {
+        let merged = self.memory.compact_adjacent_transactions();
+        if merged > 0 {
+            self.push_history_rewritten();
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PagedMemory;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn stream_events() {
+        let mut memory = StreamingMemory::new(PagedMemory::<4, 4>::new(0xab), EventQueue::new());
+        assert_eq!(memory.next_event(), None);
+
+        memory.add_transaction(0x1, vec![0, 1], 0x10).unwrap();
+        memory.write_fill(0x4, 2, 2, 0x14).unwrap();
+        memory.move_to_transaction(TransactionId(0)).unwrap();
+        memory.next_transaction().unwrap();
+        assert!(memory.previous_transaction().is_ok());
+        assert!(memory.previous_transaction().is_err());
+
+        let events: Vec<_> = std::iter::from_fn(|| memory.next_event()).collect();
+        assert_eq!(
+            events,
+            vec![
+                MemoryEvent::Write {
+                    id: TransactionId(1),
+                    addr: 0x1,
+                    data: vec![0, 1],
+                    code_location: 0x10,
+                },
+                MemoryEvent::Write {
+                    id: TransactionId(2),
+                    addr: 0x4,
+                    data: vec![2, 2],
+                    code_location: 0x14,
+                },
+                MemoryEvent::Undo {
+                    id: TransactionId(2)
+                },
+                MemoryEvent::Undo {
+                    id: TransactionId(1)
+                },
+                MemoryEvent::Redo {
+                    id: TransactionId(1)
+                },
+                MemoryEvent::Undo {
+                    id: TransactionId(1)
+                },
+            ]
+        );
+        assert_eq!(memory.next_event(), None);
+        assert_eq!(memory.memory().read(0x0, 4), vec![0xab; 4]);
+    }
+
+    #[test]
+    fn stream_history_events() {
+        let mut memory = StreamingMemory::new(PagedMemory::<4, 4>::new(0xab), EventQueue::new());
+        memory.add_transaction(0x1, vec![0, 1], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![2, 3], 0x0).unwrap();
+        memory.add_transaction(0x8, vec![4], 0x0).unwrap();
+        while memory.next_event().is_some() {}
+
+        memory.rebase(0xab);
+        memory.rebase(0x00);
+        assert_eq!(memory.compact_adjacent_transactions(), 1);
+        memory.truncate_transaction_log(5);
+        memory.truncate_transaction_log(1);
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.clear_future_transactions(), 1);
+        assert_eq!(memory.clear_future_transactions(), 0);
+
+        let events: Vec<_> = std::iter::from_fn(|| memory.next_event()).collect();
+        assert_eq!(
+            events,
+            vec![
+                MemoryEvent::Rebase {
+                    default_value: 0x00
+                },
+                MemoryEvent::HistoryRewritten {
+                    id: TransactionId(2)
+                },
+                MemoryEvent::HistoryRewritten {
+                    id: TransactionId(1)
+                },
+                MemoryEvent::Undo {
+                    id: TransactionId(1)
+                },
+                MemoryEvent::HistoryRewritten {
+                    id: TransactionId(0)
+                },
+            ]
+        );
+        assert_eq!(memory.memory().read(0x0, 4), vec![0x00, 0, 2, 3]);
+    }
+}
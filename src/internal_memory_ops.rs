@@ -1,28 +1,535 @@
-use crate::TransactionId;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
+use crate::{MemoryError, TransactionId, TransactionStorage, CHUNK_SIZE};
+
+mod growable_memory;
 mod paged_memory;
-pub use paged_memory::PagedMemory;
+mod paged_memory_builder;
+mod params;
+pub use growable_memory::GrowableMemory;
+pub use paged_memory::{Page, PagedMemory, PagedMemoryIntoIter, PagedMemoryIter};
+pub use paged_memory_builder::PagedMemoryBuilder;
+pub(crate) use params::{validate_paging_params, ParamsError};
 
 #[cfg(feature = "naive")]
 mod naive_memory;
 #[cfg(feature = "naive")]
 pub use naive_memory::NaiveMemory;
 
+/// Storage primitives that every [`crate::Memory`] is built on. Kept crate-private;
+/// external storage plugs in through [`crate::MemoryBackend`].
 pub trait InternalMemoryOps {
+    /// Entry type of the transaction log.
+    type TransactionStorage: TransactionStorage;
+
+    fn transactions(&self) -> &[Self::TransactionStorage];
+    fn transactions_mut(&mut self) -> &mut Vec<Self::TransactionStorage>;
     fn transaction_vec_len(&self) -> usize;
-    fn transaction_vec_push(&mut self, transaction: Transaction);
-    fn get_mut_transaction(&mut self, idx: usize) -> Option<&mut Transaction>;
+    fn transaction_vec_capacity(&self) -> usize;
+    fn transaction_vec_push(&mut self, transaction: Self::TransactionStorage);
+    /// `None` if `idx` is past the end of the log.
+    fn get_mut_transaction(&mut self, idx: usize) -> Option<&mut Self::TransactionStorage>;
     fn set_transaction_idx(&mut self, idx: usize);
     fn write_data(&mut self, addr: usize, data: &[u8]);
     fn write_transaction_ids(&mut self, addr: usize, transaction_ids: &[TransactionId]);
-    fn address_space_size(&self) -> usize;
+    /// Resets the id of every byte to [`TransactionId::INITIAL`], keeping the data. Bytes
+    /// that had another id become initialized.
+    fn clear_transaction_ids(&mut self);
+    /// Replaces the id of every byte with `map(id)`. Bytes mapped to
+    /// [`TransactionId::INITIAL`] from another id become initialized.
+    fn remap_transaction_ids(&mut self, map: &dyn Fn(TransactionId) -> TransactionId);
+    fn default_value(&self) -> u8;
+    /// Whether the byte at `addr` may hold content that no transaction in the log wrote,
+    /// such as a loaded image or the result of a dropped transaction.
+    fn is_initialized(&self, addr: usize) -> bool;
+    /// Changes the default value. Bytes that were never written, meaning they have id
+    /// [`TransactionId::INITIAL`] and are not initialized, read as the new one afterwards.
+    fn replace_default_value(&mut self, new_default: u8);
+    /// Records and applies a write. Concrete memories call `record_transaction`;
+    /// wrappers forward to the inner memory and observe the result, so every write path,
+    /// including fills, goes through here.
+    fn add_transaction_data(
+        &mut self,
+        addr: usize,
+        data: TransactionData,
+        code_location: usize,
+    ) -> Result<(), MemoryError>;
+    #[cfg(feature = "protection")]
+    fn protection(&self) -> &crate::protection::ProtectionMap;
+    #[cfg(feature = "protection")]
+    fn protection_mut(&mut self) -> &mut crate::protection::ProtectionMap;
+}
+
+/// Feeds `len` copies of `value` to `visitor` without allocating.
+pub(crate) fn visit_default(value: u8, len: usize, visitor: &mut dyn FnMut(&[u8])) {
+    let defaults = [value; 256];
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = remaining.min(defaults.len());
+        visitor(&defaults[..chunk]);
+        remaining -= chunk;
+    }
+}
+
+/// Offsets of one page that may hold content no logged transaction wrote.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub(crate) enum InitializedBytes {
+    #[default]
+    Empty,
+    /// `count` is the number of `true` entries in `bytes`.
+    Partial {
+        bytes: Box<[bool]>,
+        count: usize,
+    },
+    Full,
+}
+
+impl InitializedBytes {
+    pub(crate) fn is_empty(&self) -> bool {
+        matches!(self, Self::Empty)
+    }
+
+    pub(crate) fn contains(&self, offset: usize) -> bool {
+        match self {
+            Self::Empty => false,
+            Self::Partial { bytes, .. } => bytes[offset],
+            Self::Full => true,
+        }
+    }
+
+    /// Marks `range` of a page of `page_size` bytes.
+    pub(crate) fn mark(&mut self, range: std::ops::Range<usize>, page_size: usize) {
+        if range.len() == page_size {
+            *self = Self::Full;
+            return;
+        }
+        if self.is_empty() {
+            *self = Self::Partial {
+                bytes: vec![false; page_size].into(),
+                count: 0,
+            };
+        }
+        if let Self::Partial { bytes, count } = self {
+            for byte in &mut bytes[range] {
+                *count += usize::from(!*byte);
+                *byte = true;
+            }
+            if *count == page_size {
+                *self = Self::Full;
+            }
+        }
+    }
+
+    /// Maximal runs of marked offsets, in ascending order.
+    pub(crate) fn ranges(&self, page_size: usize) -> Vec<std::ops::Range<usize>> {
+        match self {
+            Self::Empty => Vec::new(),
+            Self::Partial { bytes, .. } => {
+                let mut ranges = Vec::new();
+                let mut start = None;
+                for (offset, &marked) in bytes.iter().chain([&false]).enumerate() {
+                    match (start, marked) {
+                        (None, true) => start = Some(offset),
+                        (Some(run_start), false) => {
+                            ranges.push(run_start..offset);
+                            start = None;
+                        }
+                        _ => {}
+                    }
+                }
+                ranges
+            }
+            Self::Full => std::iter::once(0..page_size).collect(),
+        }
+    }
 }
 
-#[derive(Debug, Default, Clone, Eq, PartialEq, Ord, PartialOrd)]
+/// Splits `addr..addr + size` into `(page_idx, in_page_range)` chunks.
+pub(crate) fn page_chunks(
+    page_size: usize,
+    addr: usize,
+    size: usize,
+) -> impl Iterator<Item = (usize, std::ops::Range<usize>)> {
+    let end = addr
+        .checked_add(size)
+        .expect("address range overflows usize");
+    let mut chunk_addr = addr;
+    std::iter::from_fn(move || {
+        if chunk_addr >= end {
+            return None;
+        }
+        let page_idx = chunk_addr / page_size;
+        let start = chunk_addr % page_size;
+        let len = (page_size - start).min(end - chunk_addr);
+        chunk_addr += len;
+        Some((page_idx, start..start + len))
+    })
+}
+
+macro_rules! delegate_internal_memory_ops {
+    ($field:ident: $inner:ty) => {
+        type TransactionStorage =
+            <$inner as $crate::internal_memory_ops::InternalMemoryOps>::TransactionStorage;
+
+        fn transactions(&self) -> &[Self::TransactionStorage] {
+            self.$field.transactions()
+        }
+
+        fn transactions_mut(&mut self) -> &mut Vec<Self::TransactionStorage> {
+            self.$field.transactions_mut()
+        }
+
+        fn transaction_vec_len(&self) -> usize {
+            self.$field.transaction_vec_len()
+        }
+
+        fn transaction_vec_capacity(&self) -> usize {
+            self.$field.transaction_vec_capacity()
+        }
+
+        fn transaction_vec_push(&mut self, transaction: Self::TransactionStorage) {
+            self.$field.transaction_vec_push(transaction)
+        }
+
+        fn get_mut_transaction(&mut self, idx: usize) -> Option<&mut Self::TransactionStorage> {
+            self.$field.get_mut_transaction(idx)
+        }
+
+        fn set_transaction_idx(&mut self, idx: usize) {
+            self.$field.set_transaction_idx(idx)
+        }
+
+        fn write_data(&mut self, addr: usize, data: &[u8]) {
+            self.$field.write_data(addr, data)
+        }
+
+        fn write_transaction_ids(
+            &mut self,
+            addr: usize,
+            transaction_ids: &[$crate::TransactionId],
+        ) {
+            self.$field.write_transaction_ids(addr, transaction_ids)
+        }
+
+        fn clear_transaction_ids(&mut self) {
+            self.$field.clear_transaction_ids()
+        }
+
+        fn remap_transaction_ids(
+            &mut self,
+            map: &dyn Fn($crate::TransactionId) -> $crate::TransactionId,
+        ) {
+            self.$field.remap_transaction_ids(map)
+        }
+
+        fn default_value(&self) -> u8 {
+            self.$field.default_value()
+        }
+
+        fn is_initialized(&self, addr: usize) -> bool {
+            self.$field.is_initialized(addr)
+        }
+
+        fn replace_default_value(&mut self, new_default: u8) {
+            self.$field.replace_default_value(new_default)
+        }
+
+        #[cfg(feature = "protection")]
+        fn protection(&self) -> &$crate::protection::ProtectionMap {
+            self.$field.protection()
+        }
+
+        #[cfg(feature = "protection")]
+        fn protection_mut(&mut self) -> &mut $crate::protection::ProtectionMap {
+            self.$field.protection_mut()
+        }
+    };
+}
+pub(crate) use delegate_internal_memory_ops;
+
+/// Bytes written by a transaction, stored as `pattern` repeated up to `len` bytes. Plain
+/// writes keep the bytes themselves; fills and repeated patterns keep a single period.
+#[derive(Debug, Default, Clone)]
+pub struct TransactionData {
+    pattern: Arc<[u8]>,
+    len: usize,
+}
+
+impl TransactionData {
+    /// `pattern` repeated up to `len` bytes. `pattern` may only be empty if `len` is 0.
+    pub(crate) fn repeated(pattern: &[u8], len: usize) -> Self {
+        assert!(len == 0 || !pattern.is_empty());
+        Self {
+            pattern: pattern.into(),
+            len,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Number of bytes kept on the heap.
+    pub(crate) fn stored_len(&self) -> usize {
+        self.pattern.len()
+    }
+
+    /// The stored bytes, unless they are a repeated pattern.
+    pub(crate) fn as_slice(&self) -> Option<&Arc<[u8]>> {
+        (self.pattern.len() == self.len).then_some(&self.pattern)
+    }
+
+    /// All bytes, expanding a repeated pattern.
+    pub(crate) fn to_bytes(&self) -> Cow<'_, [u8]> {
+        match self.as_slice() {
+            Some(bytes) => Cow::Borrowed(bytes),
+            None => {
+                let mut bytes = vec![0; self.len];
+                self.copy_to(0, &mut bytes);
+                Cow::Owned(bytes)
+            }
+        }
+    }
+
+    /// Copies `dst.len()` bytes starting at `offset` into `dst`.
+    pub(crate) fn copy_to(&self, offset: usize, dst: &mut [u8]) {
+        if let Some(bytes) = self.as_slice() {
+            dst.copy_from_slice(&bytes[offset..offset + dst.len()]);
+            return;
+        }
+        let period = self.pattern.len();
+        let mut phase = offset % period;
+        let mut filled = 0;
+        while filled < dst.len() {
+            let len = (period - phase).min(dst.len() - filled);
+            dst[filled..filled + len].copy_from_slice(&self.pattern[phase..phase + len]);
+            filled += len;
+            phase = 0;
+        }
+    }
+
+    /// Feeds the bytes to `visitor` in pieces of at most [`CHUNK_SIZE`] bytes, together with
+    /// their offsets. A repeated pattern is expanded one piece at a time.
+    pub(crate) fn visit_chunks(&self, visitor: &mut dyn FnMut(usize, &[u8])) {
+        let mut buf = Vec::new();
+        for offset in (0..self.len).step_by(CHUNK_SIZE) {
+            let size = CHUNK_SIZE.min(self.len - offset);
+            match self.as_slice() {
+                Some(bytes) => visitor(offset, &bytes[offset..offset + size]),
+                None => {
+                    buf.resize(size, 0);
+                    self.copy_to(offset, &mut buf);
+                    visitor(offset, &buf);
+                }
+            }
+        }
+    }
+
+    fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.pattern.iter().copied().cycle().take(self.len)
+    }
+}
+
+impl From<Vec<u8>> for TransactionData {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::from(Arc::<[u8]>::from(bytes))
+    }
+}
+
+impl From<&[u8]> for TransactionData {
+    fn from(bytes: &[u8]) -> Self {
+        Self::from(Arc::<[u8]>::from(bytes))
+    }
+}
+
+impl From<Arc<[u8]>> for TransactionData {
+    fn from(bytes: Arc<[u8]>) -> Self {
+        Self {
+            len: bytes.len(),
+            pattern: bytes,
+        }
+    }
+}
+
+// Equal contents compare equal however they are stored.
+impl PartialEq for TransactionData {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.as_slice(), other.as_slice()) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.len == other.len && self.bytes().eq(other.bytes()),
+        }
+    }
+}
+
+impl Eq for TransactionData {}
+
+impl PartialOrd for TransactionData {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TransactionData {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.as_slice(), other.as_slice()) {
+            (Some(a), Some(b)) => a.cmp(b),
+            _ => self.bytes().cmp(other.bytes()),
+        }
+    }
+}
+
+impl Hash for TransactionData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+        self.visit_chunks(&mut |_, chunk| state.write(chunk));
+    }
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct Transaction {
-    pub addr: usize,
-    pub data: Vec<u8>,
-    pub old_data: Vec<u8>,
-    pub old_ids: Vec<TransactionId>,
-    pub code_location: usize,
+    pub(crate) addr: usize,
+    pub(crate) data: TransactionData,
+    pub(crate) old_data: Arc<[u8]>,
+    pub(crate) old_ids: Vec<TransactionId>,
+    pub(crate) code_location: usize,
+    pub(crate) annotations: HashMap<String, String>,
+}
+
+impl Transaction {
+    /// First address written by the transaction.
+    pub fn address(&self) -> usize {
+        self.addr
+    }
+
+    /// Bytes written by the transaction. Fills are stored compactly and expanded here.
+    pub fn new_data(&self) -> Cow<'_, [u8]> {
+        self.data.to_bytes()
+    }
+
+    /// Bytes the transaction overwrote, restored when it is reverted.
+    pub fn previous_data(&self) -> &[u8] {
+        &self.old_data
+    }
+
+    /// Ids of the transactions that last wrote each overwritten byte. Always as long as
+    /// [`Transaction::new_data`].
+    pub fn previous_transaction_ids(&self) -> &[TransactionId] {
+        &self.old_ids
+    }
+
+    /// Code location passed to [`crate::Memory::add_transaction`].
+    pub fn instruction_pointer(&self) -> usize {
+        self.code_location
+    }
+
+    pub fn annotations(&self) -> &HashMap<String, String> {
+        &self.annotations
+    }
+
+    pub fn byte_count(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn overlaps(&self, other: &Transaction) -> bool {
+        self.overlaps_range(other.addr, other.byte_count())
+    }
+
+    /// Whether the transaction wrote any byte of `addr..addr + size`. Range ends past
+    /// `usize::MAX` saturate.
+    pub(crate) fn overlaps_range(&self, addr: usize, size: usize) -> bool {
+        size > 0
+            && self.addr < addr.saturating_add(size)
+            && addr < self.addr.saturating_add(self.byte_count())
+    }
+
+    /// Whether the two transactions cover one contiguous range when combined.
+    pub(crate) fn touches(&self, other: &Transaction) -> bool {
+        self.addr <= other.addr + other.byte_count() && other.addr <= self.addr + self.byte_count()
+    }
+
+    /// Combines `a` and the transaction `b` applied right after it into a single transaction.
+    ///
+    /// Returns `None` if there is a gap between their address ranges. The result keeps the
+    /// code location of `a`; annotations of `b` take precedence over those of `a`.
+    pub fn merge(a: Transaction, b: Transaction) -> Option<Transaction> {
+        if !a.touches(&b) {
+            return None;
+        }
+        let addr = a.addr.min(b.addr);
+        let end = (a.addr + a.byte_count()).max(b.addr + b.byte_count());
+        let mut data = vec![0; end - addr];
+        let mut old_data = vec![0; end - addr];
+        let mut old_ids = vec![TransactionId(0); end - addr];
+        for transaction in [&b, &a] {
+            let range = transaction.addr - addr..transaction.addr - addr + transaction.byte_count();
+            old_data[range.clone()].copy_from_slice(&transaction.old_data);
+            old_ids[range].copy_from_slice(&transaction.old_ids);
+        }
+        for transaction in [&a, &b] {
+            let range = transaction.addr - addr..transaction.addr - addr + transaction.byte_count();
+            transaction.data.copy_to(0, &mut data[range]);
+        }
+        let mut annotations = a.annotations;
+        annotations.extend(b.annotations);
+        Some(Transaction {
+            addr,
+            data: data.into(),
+            old_data: old_data.into(),
+            old_ids,
+            code_location: a.code_location,
+            annotations,
+        })
+    }
+
+    fn comparison_key(&self) -> (usize, &TransactionData, &[u8], &[TransactionId], usize) {
+        (
+            self.addr,
+            &self.data,
+            &self.old_data,
+            &self.old_ids,
+            self.code_location,
+        )
+    }
+}
+
+/// Inclusive `(start, end)` address range written by both transactions, or `None` if they
+/// are disjoint.
+pub fn intersect_transaction_ranges(a: &Transaction, b: &Transaction) -> Option<(usize, usize)> {
+    if !a.overlaps(b) {
+        return None;
+    }
+    let start = a.addr.max(b.addr);
+    let end = (a.addr + a.byte_count()).min(b.addr + b.byte_count()) - 1;
+    Some((start, end))
+}
+
+// Annotations are debugging metadata and do not take part in comparisons.
+impl PartialEq for Transaction {
+    fn eq(&self, other: &Self) -> bool {
+        self.comparison_key() == other.comparison_key()
+    }
+}
+
+impl Eq for Transaction {}
+
+impl PartialOrd for Transaction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Transaction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.comparison_key().cmp(&other.comparison_key())
+    }
+}
+
+impl Hash for Transaction {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.comparison_key().hash(state);
+    }
 }
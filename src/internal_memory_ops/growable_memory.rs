@@ -0,0 +1,316 @@
+use super::page_chunks;
+use super::validate_paging_params;
+use super::visit_default;
+use super::InitializedBytes;
+use super::InternalMemoryOps;
+use super::Transaction;
+use super::TransactionData;
+use crate::Memory;
+use crate::MemoryError;
+use crate::TransactionId;
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+struct DynamicPage {
+    data: Box<[u8]>,
+    transaction_ids: Box<[TransactionId]>,
+    /// Bytes that may hold content no logged transaction wrote.
+    initialized: InitializedBytes,
+}
+
+/// Paged memory whose geometry is chosen at runtime and whose address space can grow.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct GrowableMemory {
+    default_value: u8,
+    page_size: usize,
+    memory: Vec<Option<DynamicPage>>,
+    #[cfg(feature = "protection")]
+    protection: crate::protection::ProtectionMap,
+    transactions: Vec<Transaction>,
+    transaction_idx: usize,
+}
+
+impl GrowableMemory {
+    pub fn new(num_pages: usize, page_size: usize, default_value: u8) -> Result<Self, MemoryError> {
+        if let Err(error) = validate_paging_params(num_pages, page_size) {
+            return Err(MemoryError::InvalidParams(error.as_str().to_owned()));
+        }
+        Ok(Self {
+            default_value,
+            page_size,
+            memory: vec![None; num_pages],
+            #[cfg(feature = "protection")]
+            protection: Default::default(),
+            transactions: Vec::new(),
+            transaction_idx: 0,
+        })
+    }
+
+    pub fn num_pages(&self) -> usize {
+        self.memory.len()
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Appends `additional_pages` unallocated pages. Existing data and history are kept.
+    ///
+    /// The new page count must be a power of two, as in [`GrowableMemory::new`].
+    pub fn grow(&mut self, additional_pages: usize) -> Result<(), MemoryError> {
+        let Some(num_pages) = self.memory.len().checked_add(additional_pages) else {
+            return Err(MemoryError::AddressOutOfRange);
+        };
+        if let Err(error) = validate_paging_params(num_pages, self.page_size) {
+            return Err(MemoryError::InvalidParams(error.as_str().to_owned()));
+        }
+        self.memory.resize(num_pages, None);
+        Ok(())
+    }
+
+    fn page_mut(&mut self, page_idx: usize) -> &mut DynamicPage {
+        let (default_value, page_size) = (self.default_value, self.page_size);
+        self.memory[page_idx].get_or_insert_with(|| DynamicPage {
+            data: vec![default_value; page_size].into(),
+            transaction_ids: vec![TransactionId(0); page_size].into(),
+            initialized: InitializedBytes::Empty,
+        })
+    }
+}
+
+impl InternalMemoryOps for GrowableMemory {
+    type TransactionStorage = Transaction;
+
+    fn write_data(&mut self, addr: usize, data: &[u8]) {
+        let mut offset = 0;
+        for (page_idx, range) in page_chunks(self.page_size, addr, data.len()) {
+            let len = range.len();
+            self.page_mut(page_idx).data[range].copy_from_slice(&data[offset..offset + len]);
+            offset += len;
+        }
+    }
+
+    fn write_transaction_ids(&mut self, addr: usize, transaction_ids: &[TransactionId]) {
+        let mut offset = 0;
+        for (page_idx, range) in page_chunks(self.page_size, addr, transaction_ids.len()) {
+            let len = range.len();
+            self.page_mut(page_idx).transaction_ids[range]
+                .copy_from_slice(&transaction_ids[offset..offset + len]);
+            offset += len;
+        }
+    }
+
+    fn clear_transaction_ids(&mut self) {
+        self.remap_transaction_ids(&|_| TransactionId(0));
+    }
+
+    fn remap_transaction_ids(&mut self, map: &dyn Fn(TransactionId) -> TransactionId) {
+        for page in self.memory.iter_mut().flatten() {
+            for (offset, id) in page.transaction_ids.iter_mut().enumerate() {
+                let new_id = map(*id);
+                if *id != TransactionId(0) && new_id == TransactionId(0) {
+                    page.initialized.mark(offset..offset + 1, self.page_size);
+                }
+                *id = new_id;
+            }
+        }
+    }
+
+    fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    fn transactions_mut(&mut self) -> &mut Vec<Transaction> {
+        &mut self.transactions
+    }
+
+    fn transaction_vec_len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    fn transaction_vec_capacity(&self) -> usize {
+        self.transactions.capacity()
+    }
+
+    fn transaction_vec_push(&mut self, transaction: Transaction) {
+        self.transactions.push(transaction);
+    }
+
+    fn get_mut_transaction(&mut self, idx: usize) -> Option<&mut Transaction> {
+        self.transactions.get_mut(idx)
+    }
+
+    fn set_transaction_idx(&mut self, idx: usize) {
+        self.transaction_idx = idx;
+    }
+
+    fn default_value(&self) -> u8 {
+        self.default_value
+    }
+
+    fn is_initialized(&self, addr: usize) -> bool {
+        self.memory[addr / self.page_size]
+            .as_ref()
+            .is_some_and(|page| page.initialized.contains(addr % self.page_size))
+    }
+
+    fn replace_default_value(&mut self, new_default: u8) {
+        let old_default = self.default_value;
+        for page in self.memory.iter_mut().flatten() {
+            if page.initialized == InitializedBytes::Full {
+                continue;
+            }
+            for (offset, (byte, id)) in page
+                .data
+                .iter_mut()
+                .zip(page.transaction_ids.iter())
+                .enumerate()
+            {
+                if *id == TransactionId(0)
+                    && *byte == old_default
+                    && !page.initialized.contains(offset)
+                {
+                    *byte = new_default;
+                }
+            }
+        }
+        self.default_value = new_default;
+    }
+
+    fn add_transaction_data(
+        &mut self,
+        addr: usize,
+        data: TransactionData,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        crate::record_transaction(self, addr, data, code_location)
+    }
+
+    #[cfg(feature = "protection")]
+    fn protection(&self) -> &crate::protection::ProtectionMap {
+        &self.protection
+    }
+
+    #[cfg(feature = "protection")]
+    fn protection_mut(&mut self) -> &mut crate::protection::ProtectionMap {
+        &mut self.protection
+    }
+}
+
+impl Memory for GrowableMemory {
+    fn read(&self, addr: usize, size: usize) -> Vec<u8> {
+        assert!(size > 0);
+        assert!(addr
+            .checked_add(size)
+            .is_some_and(|end| end <= self.address_space_size()));
+        let mut data = Vec::with_capacity(size);
+        for (page_idx, range) in page_chunks(self.page_size, addr, size) {
+            match &self.memory[page_idx] {
+                Some(page) => data.extend_from_slice(&page.data[range]),
+                None => data.resize(data.len() + range.len(), self.default_value),
+            }
+        }
+        data
+    }
+
+    fn visit_data(&self, addr: usize, size: usize, visitor: &mut dyn FnMut(&[u8])) {
+        assert!(addr
+            .checked_add(size)
+            .is_some_and(|end| end <= self.address_space_size()));
+        for (page_idx, range) in page_chunks(self.page_size, addr, size) {
+            match &self.memory[page_idx] {
+                Some(page) => visitor(&page.data[range]),
+                None => visit_default(self.default_value, range.len(), visitor),
+            }
+        }
+    }
+
+    fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId> {
+        assert!(size > 0);
+        assert!(addr
+            .checked_add(size)
+            .is_some_and(|end| end <= self.address_space_size()));
+        let mut ids = Vec::with_capacity(size);
+        for (page_idx, range) in page_chunks(self.page_size, addr, size) {
+            match &self.memory[page_idx] {
+                Some(page) => ids.extend_from_slice(&page.transaction_ids[range]),
+                None => ids.resize(ids.len() + range.len(), TransactionId(0)),
+            }
+        }
+        ids
+    }
+
+    fn current_transaction_id(&self) -> usize {
+        self.transaction_idx
+    }
+
+    fn address_space_size(&self) -> usize {
+        self.memory.len() * self.page_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PagedMemory;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn new() {
+        assert_eq!(
+            GrowableMemory::new(4, 6, 0xab),
+            Err(MemoryError::InvalidParams(
+                "PAGE_SIZE must be a power of two, got 6".to_owned()
+            ))
+        );
+        let memory = GrowableMemory::new(4, 4, 0xab).unwrap();
+        assert_eq!(memory.address_space_size(), 16);
+        assert_eq!(memory.read(0x0, 16), vec![0xab; 16]);
+    }
+
+    #[test]
+    fn matches_paged_memory() {
+        let mut growable = GrowableMemory::new(4, 4, 0xab).unwrap();
+        let mut paged = PagedMemory::<4, 4>::new(0xab);
+        for (addr, data) in [
+            (0x2, vec![1, 2, 3, 4, 5, 6]),
+            (0x0, vec![7]),
+            (0xf, vec![8]),
+        ] {
+            growable.add_transaction(addr, data.clone(), 0x0).unwrap();
+            paged.add_transaction(addr, data, 0x0).unwrap();
+        }
+        assert!(growable.memory_eq(&paged));
+        growable.previous_transaction().unwrap();
+        paged.previous_transaction().unwrap();
+        assert!(growable.memory_eq(&paged));
+    }
+
+    #[test]
+    fn grow() {
+        let mut memory = GrowableMemory::new(2, 4, 0xab).unwrap();
+        memory.add_transaction(0x6, vec![1, 2], 0x0).unwrap();
+        assert_eq!(
+            memory.add_transaction(0x7, vec![3, 4], 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+
+        assert!(matches!(memory.grow(3), Err(MemoryError::InvalidParams(_))));
+        assert_eq!(memory.num_pages(), 2);
+
+        memory.grow(6).unwrap();
+        assert_eq!(memory.num_pages(), 8);
+        assert_eq!(memory.address_space_size(), 32);
+        assert_eq!(memory.read(0x6, 4), vec![1, 2, 0xab, 0xab]);
+        assert_eq!(memory.read(0x10, 4), vec![0xab; 4]);
+
+        memory.add_transaction(0x7, vec![3, 4], 0x0).unwrap();
+        memory.add_transaction(0x13, vec![5], 0x0).unwrap();
+        assert_eq!(memory.read(0x6, 3), vec![1, 3, 4]);
+        memory.move_to_transaction(TransactionId(0)).unwrap();
+        assert_eq!(memory.read(0x0, 32), vec![0xab; 32]);
+        memory.move_to_transaction(TransactionId(3)).unwrap();
+        assert_eq!(memory.read(0x13, 1), vec![5]);
+
+        assert_eq!(memory.grow(usize::MAX), Err(MemoryError::AddressOutOfRange));
+    }
+}
@@ -1,14 +1,24 @@
+use std::ops::Index;
+
 use crate::TransactionId;
 
 use super::InternalMemoryOps;
 use super::Transaction;
+use super::TransactionData;
+use super::{validate_paging_params, ParamsError};
 use crate::Memory;
+use crate::MemoryError;
 
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct NaiveMemory<const NUM_PAGES: usize, const PAGE_SIZE: usize, const SIZE: usize> {
     default_value: u8,
     data: Box<[u8; SIZE]>,
     transaction_ids: Box<[TransactionId; SIZE]>,
+    /// Bytes that may hold content no logged transaction wrote, tracked like in
+    /// [`super::PagedMemory`].
+    initialized: Box<[bool; SIZE]>,
+    #[cfg(feature = "protection")]
+    protection: crate::protection::ProtectionMap,
     transactions: Vec<Transaction>,
     transaction_idx: usize,
 }
@@ -16,31 +26,143 @@ pub struct NaiveMemory<const NUM_PAGES: usize, const PAGE_SIZE: usize, const SIZ
 impl<const NUM_PAGES: usize, const PAGE_SIZE: usize, const SIZE: usize>
     NaiveMemory<NUM_PAGES, PAGE_SIZE, SIZE>
 {
-    const COMPTIME_SIZE_CHECK_PAGE: () = assert!(2_usize.pow(PAGE_SIZE.ilog2()) == PAGE_SIZE);
-    const COMPTIME_SIZE_CHECK_SPACE: () = assert!(2_usize.pow(NUM_PAGES.ilog2()) == NUM_PAGES);
-    const COMPTIME_SIZE_CHECK_SIZE: () = assert!(NUM_PAGES * PAGE_SIZE == SIZE);
+    const COMPTIME_PARAMS_CHECK: () = if let Err(error) = Self::validate_params() {
+        panic!("{}", error.as_str())
+    };
+
+    const fn validate_params() -> Result<(), ParamsError> {
+        if let Err(error) = validate_paging_params(NUM_PAGES, PAGE_SIZE) {
+            return Err(error);
+        }
+        if NUM_PAGES * PAGE_SIZE != SIZE {
+            return Err(
+                ParamsError::new("SIZE must be equal to NUM_PAGES * PAGE_SIZE").with_value(SIZE),
+            );
+        }
+        Ok(())
+    }
 
     pub fn new(default_value: u8) -> Self {
-        let _: () = Self::COMPTIME_SIZE_CHECK_PAGE;
-        let _: () = Self::COMPTIME_SIZE_CHECK_SPACE;
-        let _: () = Self::COMPTIME_SIZE_CHECK_SIZE;
+        let _: () = Self::COMPTIME_PARAMS_CHECK;
         Self {
             default_value,
             data: Box::new([default_value; SIZE]),
             transaction_ids: Box::new([TransactionId(0); SIZE]),
+            initialized: Box::new([false; SIZE]),
+            #[cfg(feature = "protection")]
+            protection: Default::default(),
             transaction_idx: 0,
             transactions: Vec::new(),
         }
     }
+
+    /// Converts into a [`PagedMemory`] with the same contents and history. Only pages that
+    /// hold something other than untouched default bytes are allocated.
+    ///
+    /// [`PagedMemory`]: super::PagedMemory
+    pub fn into_paged(self) -> super::PagedMemory<NUM_PAGES, PAGE_SIZE> {
+        let mut memory = super::PagedMemory::new(self.default_value);
+        for page_idx in 0..NUM_PAGES {
+            let range = page_idx * PAGE_SIZE..(page_idx + 1) * PAGE_SIZE;
+            let untouched = self.data[range.clone()]
+                .iter()
+                .all(|&byte| byte == self.default_value)
+                && self.transaction_ids[range.clone()]
+                    .iter()
+                    .all(|&id| id == TransactionId(0));
+            if !untouched {
+                memory.write_data(range.start, &self.data[range.clone()]);
+                memory.write_transaction_ids(range.start, &self.transaction_ids[range]);
+            }
+        }
+        for (addr, _) in self
+            .initialized
+            .iter()
+            .enumerate()
+            .filter(|(_, &init)| init)
+        {
+            memory.mark_initialized(addr, 1);
+        }
+        memory.clear_dirty_flags();
+        #[cfg(feature = "protection")]
+        {
+            *memory.protection_mut() = self.protection;
+        }
+        *memory.transactions_mut() = self.transactions;
+        memory.set_transaction_idx(self.transaction_idx);
+        memory
+    }
+
+    pub fn from_slice(data: &[u8], default_value: u8) -> Result<Self, MemoryError> {
+        if data.len() != SIZE {
+            return Err(MemoryError::SizeMismatch {
+                expected: SIZE,
+                actual: data.len(),
+            });
+        }
+        let mut memory = Self::new(default_value);
+        memory.data.copy_from_slice(data);
+        memory.initialized.fill(true);
+        Ok(memory)
+    }
+
+    pub(crate) fn mark_initialized(&mut self, addr: usize, size: usize) {
+        self.initialized[addr..addr + size].fill(true);
+    }
+
+    /// Memory contents at the current position in history.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data[..]
+    }
+
+    /// Ids of the transactions that last wrote each byte, at the current position in history.
+    pub fn transaction_ids_as_slice(&self) -> &[TransactionId] {
+        &self.transaction_ids[..]
+    }
+
+    /// Mutable access to the memory contents, bypassing the transaction log.
+    ///
+    /// # Safety
+    ///
+    /// Writes through the returned slice are not recorded. The caller must make sure the
+    /// modified bytes are not covered by any transaction in the log, otherwise undoing or
+    /// redoing such a transaction restores values that no longer match the memory contents.
+    pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.data[..]
+    }
+}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize, const SIZE: usize> Default
+    for NaiveMemory<NUM_PAGES, PAGE_SIZE, SIZE>
+{
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// Reads the byte at the current position in history.
+///
+/// There is deliberately no `IndexMut`: every write has to be recorded, so use
+/// [`Memory::add_transaction`] instead.
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize, const SIZE: usize> Index<usize>
+    for NaiveMemory<NUM_PAGES, PAGE_SIZE, SIZE>
+{
+    type Output = u8;
+
+    fn index(&self, addr: usize) -> &u8 {
+        &self.data[addr]
+    }
 }
 
 impl<const NUM_PAGES: usize, const PAGE_SIZE: usize, const SIZE: usize> InternalMemoryOps
     for NaiveMemory<NUM_PAGES, PAGE_SIZE, SIZE>
 {
+    type TransactionStorage = Transaction;
+
     fn write_data(&mut self, addr: usize, data: &[u8]) {
-        for (mem_cell, value) in self.data[addr..].iter_mut().zip(data.iter()) {
-            *mem_cell = *value;
-        }
+        let dst = &mut self.data[addr..];
+        let len = dst.len().min(data.len());
+        dst[..len].copy_from_slice(&data[..len]);
     }
 
     fn write_transaction_ids(&mut self, addr: usize, transaction_ids: &[TransactionId]) {
@@ -51,9 +173,30 @@ impl<const NUM_PAGES: usize, const PAGE_SIZE: usize, const SIZE: usize> Internal
             *id_cell = *value;
         }
     }
+
+    fn clear_transaction_ids(&mut self) {
+        self.remap_transaction_ids(&|_| TransactionId(0));
+    }
+
+    fn remap_transaction_ids(&mut self, map: &dyn Fn(TransactionId) -> TransactionId) {
+        for (addr, id) in self.transaction_ids.iter_mut().enumerate() {
+            let new_id = map(*id);
+            self.initialized[addr] |= *id != TransactionId(0) && new_id == TransactionId(0);
+            *id = new_id;
+        }
+    }
+    fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+    fn transactions_mut(&mut self) -> &mut Vec<Transaction> {
+        &mut self.transactions
+    }
     fn transaction_vec_len(&self) -> usize {
         self.transactions.len()
     }
+    fn transaction_vec_capacity(&self) -> usize {
+        self.transactions.capacity()
+    }
     fn transaction_vec_push(&mut self, transaction: Transaction) {
         self.transactions.push(transaction)
     }
@@ -63,8 +206,41 @@ impl<const NUM_PAGES: usize, const PAGE_SIZE: usize, const SIZE: usize> Internal
     fn set_transaction_idx(&mut self, idx: usize) {
         self.transaction_idx = idx;
     }
-    fn address_space_size(&self) -> usize {
-        SIZE
+    fn default_value(&self) -> u8 {
+        self.default_value
+    }
+    fn is_initialized(&self, addr: usize) -> bool {
+        self.initialized[addr]
+    }
+    fn replace_default_value(&mut self, new_default: u8) {
+        let old_default = self.default_value;
+        for (addr, (byte, id)) in self
+            .data
+            .iter_mut()
+            .zip(self.transaction_ids.iter())
+            .enumerate()
+        {
+            if *id == TransactionId(0) && *byte == old_default && !self.initialized[addr] {
+                *byte = new_default;
+            }
+        }
+        self.default_value = new_default;
+    }
+    fn add_transaction_data(
+        &mut self,
+        addr: usize,
+        data: TransactionData,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        crate::record_transaction(self, addr, data, code_location)
+    }
+    #[cfg(feature = "protection")]
+    fn protection(&self) -> &crate::protection::ProtectionMap {
+        &self.protection
+    }
+    #[cfg(feature = "protection")]
+    fn protection_mut(&mut self) -> &mut crate::protection::ProtectionMap {
+        &mut self.protection
     }
 }
 
@@ -73,16 +249,156 @@ impl<const NUM_PAGES: usize, const PAGE_SIZE: usize, const SIZE: usize> Memory
 {
     fn read(&self, addr: usize, size: usize) -> Vec<u8> {
         assert!(size > 0);
-        assert!(addr + size < SIZE);
+        assert!(addr + size <= SIZE);
         self.data[addr..addr + size].to_vec()
     }
 
+    fn visit_data(&self, addr: usize, size: usize, visitor: &mut dyn FnMut(&[u8])) {
+        visitor(&self.data[addr..addr + size]);
+    }
+
     fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId> {
         assert!(size > 0);
+        assert!(addr + size <= SIZE);
         self.transaction_ids[addr..addr + size].to_vec()
     }
 
     fn current_transaction_id(&self) -> usize {
         self.transaction_idx
     }
+
+    fn address_space_size(&self) -> usize {
+        SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn as_slice() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x2, vec![1, 2], 0x0).unwrap();
+
+        let mut expected = [0xab; 16];
+        expected[0x2..0x4].copy_from_slice(&[1, 2]);
+        assert_eq!(memory.as_slice(), &expected);
+        let mut expected_ids = [TransactionId(0); 16];
+        expected_ids[0x2..0x4].fill(TransactionId(1));
+        assert_eq!(memory.transaction_ids_as_slice(), &expected_ids);
+
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.as_slice(), &[0xab; 16]);
+        assert_eq!(memory.transaction_ids_as_slice(), &[TransactionId(0); 16]);
+
+        unsafe { memory.as_mut_slice()[0xf] = 0x42 };
+        assert_eq!(memory.read(0xf, 1), vec![0x42]);
+        assert_eq!(memory.read_transaction_ids(0xf, 1), vec![TransactionId(0)]);
+    }
+
+    #[test]
+    fn write_transaction_ids_through_trait_object() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        let ops: &mut dyn InternalMemoryOps<TransactionStorage = Transaction> = &mut memory;
+        ops.write_transaction_ids(0x3, &[TransactionId(1), TransactionId(2)]);
+        ops.write_transaction_ids(0xf, &[TransactionId(3)]);
+
+        let mut expected = [TransactionId(0); 16];
+        expected[0x3] = TransactionId(1);
+        expected[0x4] = TransactionId(2);
+        expected[0xf] = TransactionId(3);
+        assert_eq!(memory.transaction_ids_as_slice(), &expected);
+    }
+
+    #[test]
+    fn read_last_byte() {
+        let memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert_eq!(memory.read(0xf, 1), vec![0xab]);
+        assert_eq!(memory.read(0x0, 16), vec![0xab; 16]);
+        assert_eq!(memory.read_transaction_ids(0xf, 1), vec![TransactionId(0)]);
+        assert_eq!(
+            memory.read_transaction_ids(0x0, 16),
+            vec![TransactionId(0); 16]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn read_transaction_ids_past_last_byte() {
+        let memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        let _ = memory.read_transaction_ids(0xf, 2);
+    }
+
+    #[test]
+    fn default() {
+        let memory = NaiveMemory::<4, 4, 16>::default();
+        assert_eq!(memory.read(0x0, 16), vec![0; 16]);
+        assert_eq!(memory.transaction_vec_len(), 0);
+    }
+
+    #[test]
+    fn index() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x2, vec![1, 2], 0x0).unwrap();
+        assert_eq!(memory[0x1], 0xab);
+        assert_eq!(memory[0x2], 1);
+        assert_eq!(memory[0x3], 2);
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory[0x2], 0xab);
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_out_of_range() {
+        let memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        let _ = memory[0x10];
+    }
+
+    #[test]
+    fn write_data_large() {
+        let mut memory = NaiveMemory::<4, 64, 256>::new(0xab);
+        let data: Vec<u8> = (0..=0xc8).collect();
+        memory.add_transaction(0x7, data.clone(), 0x0).unwrap();
+        assert_eq!(memory.read(0x7, data.len()), data);
+        assert_eq!(memory.read(0x0, 7), vec![0xab; 7]);
+        assert_eq!(
+            memory.read(0x7 + data.len(), 0x100 - 0x7 - data.len()),
+            vec![0xab; 0x100 - 0x7 - data.len()]
+        );
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.as_slice(), &[0xab; 256]);
+    }
+
+    #[test]
+    fn from_slice() {
+        let data: Vec<u8> = (0..16).collect();
+        let mut memory = NaiveMemory::<4, 4, 16>::from_slice(&data, 0xab).unwrap();
+        assert_eq!(memory.read(0x0, 16), data);
+        assert_eq!(
+            memory.read_transaction_ids(0x0, 16),
+            vec![TransactionId(0); 16]
+        );
+        assert_eq!(memory.transaction_vec_len(), 0);
+        assert_eq!(
+            memory.previous_transaction(),
+            Err(MemoryError::NoPreviousTransaction)
+        );
+
+        assert_eq!(
+            NaiveMemory::<4, 4, 16>::from_slice(&data[..15], 0xab),
+            Err(MemoryError::SizeMismatch {
+                expected: 16,
+                actual: 15
+            })
+        );
+    }
+
+    const _: () = {
+        const fn assert_send<T: Send>() {}
+        const fn assert_sync<T: Sync>() {}
+        assert_send::<NaiveMemory<4, 4, 16>>();
+        assert_sync::<NaiveMemory<4, 4, 16>>();
+    };
 }
@@ -1,10 +1,23 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use super::page_chunks;
+use super::visit_default;
+use super::InitializedBytes;
 use super::InternalMemoryOps;
 use super::Transaction;
+use super::TransactionData;
+use super::{validate_paging_params, ParamsError};
 use crate::Memory;
+use crate::MemoryError;
 use crate::TransactionId;
 
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
-struct Page<const SIZE: usize> {
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Page<const SIZE: usize> {
     data: [u8; SIZE],
     transaction_ids: [TransactionId; SIZE],
 }
@@ -15,31 +28,561 @@ impl<const SIZE: usize> Page<SIZE> {
             transaction_ids: [TransactionId(0); SIZE],
         }
     }
+
+    pub fn data(&self) -> &[u8; SIZE] {
+        &self.data
+    }
+
+    pub fn transaction_ids(&self) -> &[TransactionId; SIZE] {
+        &self.transaction_ids
+    }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Clone)]
 pub struct PagedMemory<const NUM_PAGES: usize, const PAGE_SIZE: usize> {
     default_value: u8,
-    memory: [Option<Box<Page<PAGE_SIZE>>>; NUM_PAGES],
+    memory: [Option<Arc<Page<PAGE_SIZE>>>; NUM_PAGES],
+    dirty_since_clear: [bool; NUM_PAGES],
+    /// Bytes that may hold content no logged transaction wrote, such as a loaded image. The
+    /// marks belong to page slots and survive freeing or swapping the page.
+    initialized: [InitializedBytes; NUM_PAGES],
+    #[cfg(feature = "protection")]
+    protection: crate::protection::ProtectionMap,
     transactions: Vec<Transaction>,
     transaction_idx: usize,
 }
 
 impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> PagedMemory<NUM_PAGES, PAGE_SIZE> {
-    const COMPTIME_SIZE_CHECK_PAGE: () = assert!(2_usize.pow(PAGE_SIZE.ilog2()) == PAGE_SIZE);
-    const COMPTIME_SIZE_CHECK_SPACE: () = assert!(2_usize.pow(NUM_PAGES.ilog2()) == NUM_PAGES);
+    const COMPTIME_PARAMS_CHECK: () = if let Err(error) = Self::validate_params() {
+        panic!("{}", error.as_str())
+    };
+
+    const fn validate_params() -> Result<(), ParamsError> {
+        validate_paging_params(NUM_PAGES, PAGE_SIZE)
+    }
 
     pub fn new(default_value: u8) -> Self {
-        let _: () = Self::COMPTIME_SIZE_CHECK_PAGE;
-        let _: () = Self::COMPTIME_SIZE_CHECK_SPACE;
+        let _: () = Self::COMPTIME_PARAMS_CHECK;
         Self {
             default_value,
             memory: std::array::from_fn(|_| None),
+            dirty_since_clear: [false; NUM_PAGES],
+            initialized: std::array::from_fn(|_| InitializedBytes::Empty),
+            #[cfg(feature = "protection")]
+            protection: Default::default(),
             transactions: Vec::new(),
             transaction_idx: 0,
         }
     }
 
+    pub fn with_capacity(default_value: u8, preallocate_pages: &[usize]) -> Self {
+        let mut memory = Self::new(default_value);
+        for &page_idx in preallocate_pages {
+            assert!(
+                page_idx < NUM_PAGES,
+                "page index {page_idx} is out of range, memory has {NUM_PAGES} pages"
+            );
+            memory.memory[page_idx].get_or_insert_with(|| Arc::new(Page::new(default_value)));
+        }
+        memory
+    }
+
+    /// Allocates every page up front so that no write has to allocate.
+    pub fn commit_all_pages(default_value: u8) -> Self {
+        let mut memory = Self::new(default_value);
+        for page in memory.memory.iter_mut() {
+            *page = Some(Arc::new(Page::new(default_value)));
+        }
+        memory
+    }
+
+    /// Orders memories by their contents, compared like the output of
+    /// [`PagedMemory::to_flat_bytes`]. Unlike the derived [`Ord`], the transaction log and
+    /// which pages are allocated are ignored.
+    pub fn content_cmp(&self, other: &Self) -> Ordering {
+        for (page_a, page_b) in self.memory.iter().zip(other.memory.iter()) {
+            let ordering = match (page_a, page_b) {
+                (Some(a), Some(b)) => a.data.cmp(&b.data),
+                (Some(a), None) => a.data.cmp(&[other.default_value; PAGE_SIZE]),
+                (None, Some(b)) => [self.default_value; PAGE_SIZE].cmp(&b.data),
+                (None, None) => self.default_value.cmp(&other.default_value),
+            };
+            if ordering.is_ne() {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+
+    pub fn to_flat_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(NUM_PAGES * PAGE_SIZE);
+        for page in self.memory.iter() {
+            match page {
+                Some(page) => bytes.extend_from_slice(&page.data),
+                None => bytes.resize(bytes.len() + PAGE_SIZE, self.default_value),
+            }
+        }
+        bytes
+    }
+
+    pub fn from_flat_bytes(bytes: &[u8], default_value: u8) -> Result<Self, MemoryError> {
+        if bytes.len() != NUM_PAGES * PAGE_SIZE {
+            return Err(MemoryError::SizeMismatch {
+                expected: NUM_PAGES * PAGE_SIZE,
+                actual: bytes.len(),
+            });
+        }
+        let mut memory = Self::new(default_value);
+        for (page_idx, chunk) in bytes.chunks_exact(PAGE_SIZE).enumerate() {
+            if chunk.iter().any(|&byte| byte != default_value) {
+                memory.write_page_data(page_idx, 0, chunk);
+            }
+        }
+        memory.initialized.fill(InitializedBytes::Full);
+        Ok(memory)
+    }
+
+    /// Repacks the memory into pages of a different size, keeping contents, history and dirty
+    /// tracking. The total address space must stay the same.
+    pub fn into_page_size<const NEW_NUM_PAGES: usize, const NEW_PAGE_SIZE: usize>(
+        self,
+    ) -> Result<PagedMemory<NEW_NUM_PAGES, NEW_PAGE_SIZE>, MemoryError> {
+        if NEW_NUM_PAGES * NEW_PAGE_SIZE != NUM_PAGES * PAGE_SIZE {
+            return Err(MemoryError::SizeMismatch {
+                expected: NUM_PAGES * PAGE_SIZE,
+                actual: NEW_NUM_PAGES * NEW_PAGE_SIZE,
+            });
+        }
+        let mut memory = PagedMemory::new(self.default_value);
+        for (page_idx, page) in self.memory.iter().enumerate() {
+            if let Some(page) = page {
+                let addr = page_idx * PAGE_SIZE;
+                memory.write_data(addr, &page.data);
+                memory.write_transaction_ids(addr, &page.transaction_ids);
+            }
+        }
+        memory.clear_dirty_flags();
+        for page_idx in self.iter_dirty_pages() {
+            let start_addr = page_idx * PAGE_SIZE;
+            let end_addr = start_addr + PAGE_SIZE - 1;
+            memory.dirty_since_clear[start_addr / NEW_PAGE_SIZE..=end_addr / NEW_PAGE_SIZE]
+                .fill(true);
+        }
+        for (page_idx, initialized) in self.initialized.iter().enumerate() {
+            for range in initialized.ranges(PAGE_SIZE) {
+                memory.mark_initialized(page_idx * PAGE_SIZE + range.start, range.len());
+            }
+        }
+        #[cfg(feature = "protection")]
+        {
+            memory.protection = self.protection;
+        }
+        memory.transactions = self.transactions;
+        memory.transaction_idx = self.transaction_idx;
+        Ok(memory)
+    }
+
+    /// Streams a raw binary image into memory starting at `base_addr`.
+    ///
+    /// This is initialization: no transactions are recorded, so call it before writing
+    /// through the transaction log. On error, the data read so far stays loaded.
+    pub fn load_bin(&mut self, mut reader: impl Read, base_addr: usize) -> Result<(), MemoryError> {
+        let size = NUM_PAGES * PAGE_SIZE;
+        if base_addr > size {
+            return Err(MemoryError::AddressOutOfRange);
+        }
+        let mut buf = vec![0; PAGE_SIZE];
+        let mut addr = base_addr;
+        while addr < size {
+            let chunk_len = PAGE_SIZE - addr % PAGE_SIZE;
+            let filled = read_up_to(&mut reader, &mut buf[..chunk_len])?;
+            if filled > 0 {
+                self.write_page_data(addr / PAGE_SIZE, addr % PAGE_SIZE, &buf[..filled]);
+                self.mark_initialized(addr, filled);
+            }
+            if filled < chunk_len {
+                return Ok(());
+            }
+            addr += filled;
+        }
+        if read_up_to(&mut reader, &mut buf[..1])? > 0 {
+            return Err(MemoryError::AddressOutOfRange);
+        }
+        Ok(())
+    }
+
+    /// Loads the `PT_LOAD` segments of an ELF image at their physical addresses, without
+    /// recording transactions. Nothing is loaded if any segment is invalid or out of range.
+    #[cfg(feature = "elf")]
+    pub fn load_elf(&mut self, image: &[u8]) -> Result<(), MemoryError> {
+        let segments = crate::elf::load_segments(image)?;
+        for segment in segments.iter() {
+            match segment.addr.checked_add(segment.mem_size) {
+                Some(end) if end <= NUM_PAGES * PAGE_SIZE => {}
+                _ => return Err(MemoryError::AddressOutOfRange),
+            }
+        }
+        for segment in segments {
+            if !segment.data.is_empty() {
+                self.load_data(segment.addr, segment.data);
+            }
+            let zeroed = segment.mem_size - segment.data.len();
+            if zeroed > 0 {
+                self.load_data(segment.addr + segment.data.len(), &vec![0; zeroed]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Materializes every page, including unallocated ones, into a [`NaiveMemory`] with the
+    /// same contents and history. `SIZE` must equal `NUM_PAGES * PAGE_SIZE`.
+    ///
+    /// [`NaiveMemory`]: super::NaiveMemory
+    #[cfg(feature = "naive")]
+    pub fn into_naive<const SIZE: usize>(self) -> super::NaiveMemory<NUM_PAGES, PAGE_SIZE, SIZE> {
+        let mut memory = super::NaiveMemory::new(self.default_value);
+        for (page_idx, page) in self.memory.iter().enumerate() {
+            if let Some(page) = page {
+                memory.write_data(page_idx * PAGE_SIZE, &page.data);
+                memory.write_transaction_ids(page_idx * PAGE_SIZE, &page.transaction_ids);
+            }
+            for range in self.initialized[page_idx].ranges(PAGE_SIZE) {
+                memory.mark_initialized(page_idx * PAGE_SIZE + range.start, range.len());
+            }
+        }
+        #[cfg(feature = "protection")]
+        {
+            *memory.protection_mut() = self.protection;
+        }
+        *memory.transactions_mut() = self.transactions;
+        memory.set_transaction_idx(self.transaction_idx);
+        memory
+    }
+
+    /// Moves the memory into the lower half of an address space twice as large, keeping
+    /// contents and history. `DOUBLE_PAGES` must equal `2 * NUM_PAGES`.
+    pub fn upcast<const DOUBLE_PAGES: usize>(self) -> PagedMemory<DOUBLE_PAGES, PAGE_SIZE> {
+        const {
+            assert!(
+                DOUBLE_PAGES == NUM_PAGES * 2,
+                "upcast must double the number of pages"
+            )
+        };
+        let mut memory = PagedMemory::new(self.default_value);
+        for (page, old_page) in memory.memory.iter_mut().zip(self.memory) {
+            *page = old_page;
+        }
+        memory.dirty_since_clear[..NUM_PAGES].copy_from_slice(&self.dirty_since_clear);
+        memory.initialized[..NUM_PAGES].clone_from_slice(&self.initialized);
+        #[cfg(feature = "protection")]
+        {
+            memory.protection = self.protection;
+        }
+        memory.transactions = self.transactions;
+        memory.transaction_idx = self.transaction_idx;
+        memory
+    }
+
+    /// Inverse of [`PagedMemory::upcast`]. Fails and hands the memory back if the upper half
+    /// holds non-default state or any logged transaction reaches into it. `HALF_PAGES` must
+    /// equal `NUM_PAGES / 2`.
+    pub fn try_downcast<const HALF_PAGES: usize>(
+        self,
+    ) -> Result<PagedMemory<HALF_PAGES, PAGE_SIZE>, Self> {
+        const {
+            assert!(
+                HALF_PAGES * 2 == NUM_PAGES,
+                "downcast must halve the number of pages"
+            )
+        };
+        let upper_half_is_default = self.memory[HALF_PAGES..].iter().flatten().all(|page| {
+            page.data.iter().all(|&byte| byte == self.default_value)
+                && page
+                    .transaction_ids
+                    .iter()
+                    .all(|&id| id == TransactionId(0))
+        });
+        let log_fits = self
+            .transactions
+            .iter()
+            .all(|transaction| transaction.addr + transaction.data.len() <= HALF_PAGES * PAGE_SIZE);
+        let upper_half_is_loaded = self.initialized[HALF_PAGES..]
+            .iter()
+            .any(|initialized| !initialized.is_empty());
+        if !upper_half_is_default || upper_half_is_loaded || !log_fits {
+            return Err(self);
+        }
+        let mut memory = PagedMemory::new(self.default_value);
+        for (page, old_page) in memory.memory.iter_mut().zip(self.memory) {
+            *page = old_page;
+        }
+        memory
+            .dirty_since_clear
+            .copy_from_slice(&self.dirty_since_clear[..HALF_PAGES]);
+        memory
+            .initialized
+            .clone_from_slice(&self.initialized[..HALF_PAGES]);
+        #[cfg(feature = "protection")]
+        {
+            memory.protection = self.protection;
+        }
+        memory.transactions = self.transactions;
+        memory.transaction_idx = self.transaction_idx;
+        Ok(memory)
+    }
+
+    /// Exchanges the contents of two pages by swapping the page entries. The swap is logged
+    /// as two transactions, one per page, so it can be undone like any other write.
+    pub fn swap_pages(
+        &mut self,
+        page_a: usize,
+        page_b: usize,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        for page_idx in [page_a, page_b] {
+            if page_idx >= NUM_PAGES {
+                return Err(MemoryError::PageOutOfRange(page_idx));
+            }
+            crate::validate_write(self, page_idx * PAGE_SIZE, PAGE_SIZE)?;
+        }
+        if page_a == page_b {
+            return Ok(());
+        }
+        let page_a_data: Arc<[u8]> = self.read(page_a * PAGE_SIZE, PAGE_SIZE).into();
+        let page_b_data: Arc<[u8]> = self.read(page_b * PAGE_SIZE, PAGE_SIZE).into();
+        for (page_idx, data, old_data) in [
+            (page_a, &page_b_data, &page_a_data),
+            (page_b, &page_a_data, &page_b_data),
+        ] {
+            #[cfg(feature = "verbose_debug")]
+            crate::trace_write(page_idx * PAGE_SIZE, PAGE_SIZE, code_location);
+            self.transactions.push(Transaction {
+                addr: page_idx * PAGE_SIZE,
+                data: Arc::clone(data).into(),
+                old_data: Arc::clone(old_data),
+                old_ids: self.read_transaction_ids(page_idx * PAGE_SIZE, PAGE_SIZE),
+                code_location,
+                annotations: HashMap::new(),
+            });
+        }
+        self.memory.swap(page_a, page_b);
+        let default_value = self.default_value;
+        for (page_idx, id) in [
+            (page_a, self.transaction_idx + 1),
+            (page_b, self.transaction_idx + 2),
+        ] {
+            self.memory[page_idx].get_or_insert_with(|| Arc::new(Page::new(default_value)));
+            self.write_transaction_ids(
+                page_idx * PAGE_SIZE,
+                &[TransactionId(id as u32); PAGE_SIZE],
+            );
+            self.dirty_since_clear[page_idx] = true;
+        }
+        self.transaction_idx += 2;
+        Ok(())
+    }
+
+    /// Overwrites a whole page with a single transaction, copying `data` straight into the
+    /// page slot.
+    pub fn write_page_bulk(
+        &mut self,
+        page_idx: usize,
+        data: &[u8; PAGE_SIZE],
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        self.replace_page_data(page_idx, &mut data.clone(), code_location)
+    }
+
+    /// Same as [`PagedMemory::write_page_bulk`], but swaps `page_data` into the page and
+    /// reuses the buffer for the previous contents instead of copying the new data in.
+    pub fn write_page_data_aligned(
+        &mut self,
+        page_idx: usize,
+        mut page_data: Box<[u8; PAGE_SIZE]>,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        self.replace_page_data(page_idx, &mut page_data, code_location)
+    }
+
+    /// Swaps `data` with the contents of the page and records the write as one transaction.
+    /// On success `data` holds the previous contents.
+    fn replace_page_data(
+        &mut self,
+        page_idx: usize,
+        data: &mut [u8; PAGE_SIZE],
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        if page_idx >= NUM_PAGES {
+            return Err(MemoryError::PageOutOfRange(page_idx));
+        }
+        let addr = page_idx * PAGE_SIZE;
+        crate::validate_write(self, addr, PAGE_SIZE)?;
+        #[cfg(feature = "verbose_debug")]
+        crate::trace_write(addr, PAGE_SIZE, code_location);
+        let default_value = self.default_value;
+        let page = self.memory[page_idx].get_or_insert_with(|| Arc::new(Page::new(default_value)));
+        let old_ids = page.transaction_ids.to_vec();
+        let page = Arc::make_mut(page);
+        std::mem::swap(&mut page.data, data);
+        page.transaction_ids = [TransactionId(self.transaction_idx as u32 + 1); PAGE_SIZE];
+        self.dirty_since_clear[page_idx] = true;
+        self.transactions.push(Transaction {
+            addr,
+            data: page.data.as_slice().into(),
+            old_data: data.as_slice().into(),
+            old_ids,
+            code_location,
+            annotations: HashMap::new(),
+        });
+        self.transaction_idx += 1;
+        Ok(())
+    }
+
+    /// Adds a transaction for every `(addr, data)` pair, stopping at the first error.
+    pub fn extend_transactional(
+        &mut self,
+        iter: impl IntoIterator<Item = (usize, Vec<u8>)>,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        for (addr, data) in iter {
+            self.add_transaction(addr, data, code_location)?;
+        }
+        Ok(())
+    }
+
+    /// Frees trailing pages that hold only default bytes never written by a transaction, and
+    /// returns the number of unallocated pages at the end of the address space.
+    ///
+    /// `NUM_PAGES` is fixed, so the result tells how much smaller the memory could be made
+    /// with [`PagedMemory::into_page_size`] or a fresh instance.
+    pub fn trim_tail(&mut self) -> usize {
+        let mut trailing = 0;
+        for page in self.memory.iter_mut().rev() {
+            if let Some(allocated) = page {
+                let untouched = allocated
+                    .data
+                    .iter()
+                    .all(|&byte| byte == self.default_value)
+                    && allocated
+                        .transaction_ids
+                        .iter()
+                        .all(|&id| id == TransactionId(0));
+                if !untouched {
+                    break;
+                }
+                *page = None;
+            }
+            trailing += 1;
+        }
+        trailing
+    }
+
+    /// Writes `data` outside the transaction log, as part of the initial image.
+    pub(crate) fn load_data(&mut self, addr: usize, data: &[u8]) {
+        self.write_data(addr, data);
+        self.mark_initialized(addr, data.len());
+    }
+
+    pub(crate) fn mark_initialized(&mut self, addr: usize, size: usize) {
+        for (page_idx, range) in page_chunks(PAGE_SIZE, addr, size) {
+            self.initialized[page_idx].mark(range, PAGE_SIZE);
+        }
+    }
+
+    /// Indices of the pages covered by `size > 0` bytes starting at `addr`.
+    #[inline(always)]
+    fn pages_containing(addr: usize, size: usize) -> RangeInclusive<usize> {
+        let page_addr_shift = PAGE_SIZE.ilog2();
+        (addr >> page_addr_shift)..=((addr + size - 1) >> page_addr_shift)
+    }
+
+    /// Iterates over `(address, byte)` pairs of the whole address space in ascending order.
+    pub fn iter(&self) -> PagedMemoryIter<'_, NUM_PAGES, PAGE_SIZE> {
+        PagedMemoryIter {
+            memory: self,
+            addr: 0,
+        }
+    }
+
+    fn byte_at(&self, addr: usize) -> u8 {
+        match &self.memory[addr / PAGE_SIZE] {
+            Some(page) => page.data[addr % PAGE_SIZE],
+            None => self.default_value,
+        }
+    }
+
+    pub fn pages(&self) -> impl Iterator<Item = (usize, Option<&Page<PAGE_SIZE>>)> {
+        self.memory
+            .iter()
+            .enumerate()
+            .map(|(idx, page)| (idx, page.as_deref()))
+    }
+
+    /// Indices of the pages whose data was written since construction or the last
+    /// [`PagedMemory::clear_dirty_flags`] call.
+    pub fn iter_dirty_pages(&self) -> impl Iterator<Item = usize> + '_ {
+        self.dirty_since_clear
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, &dirty)| dirty.then_some(idx))
+    }
+
+    pub fn clear_dirty_flags(&mut self) {
+        self.dirty_since_clear.fill(false);
+    }
+
+    fn comparison_key(&self) -> impl Ord + Hash + '_ {
+        #[cfg(feature = "protection")]
+        let protection = Some(&self.protection);
+        #[cfg(not(feature = "protection"))]
+        let protection = None::<()>;
+        (
+            self.default_value,
+            &self.memory,
+            &self.initialized,
+            protection,
+            &self.transactions,
+            self.transaction_idx,
+        )
+    }
+
+    pub fn read_page_data_into_buffer(
+        &self,
+        page_idx: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, MemoryError> {
+        let page = self
+            .memory
+            .get(page_idx)
+            .ok_or(MemoryError::PageOutOfRange(page_idx))?;
+        if buf.len() < PAGE_SIZE {
+            return Err(MemoryError::BufferTooSmall {
+                required: PAGE_SIZE,
+                actual: buf.len(),
+            });
+        }
+        match page {
+            Some(page) => buf[..PAGE_SIZE].copy_from_slice(&page.data),
+            None => buf[..PAGE_SIZE].fill(self.default_value),
+        }
+        Ok(PAGE_SIZE)
+    }
+
+    pub fn page_transaction_id_histogram(&self, page_idx: usize) -> HashMap<TransactionId, usize> {
+        let mut histogram = HashMap::new();
+        match &self.memory[page_idx] {
+            Some(page) => {
+                for id in page.transaction_ids.iter() {
+                    *histogram.entry(*id).or_insert(0) += 1;
+                }
+            }
+            None => {
+                histogram.insert(TransactionId(0), PAGE_SIZE);
+            }
+        }
+        histogram
+    }
+
     #[inline(always)]
     fn read_page_data(
         &self,
@@ -70,7 +613,11 @@ impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> PagedMemory<NUM_PAGES, PAGE
 
     #[inline(always)]
     fn write_page_data(&mut self, idx: usize, in_page_start_addr: usize, data: &[u8]) {
-        if let Some(page_data) = self.memory[idx].as_mut().map(|page| &mut page.data) {
+        self.dirty_since_clear[idx] = true;
+        if let Some(page_data) = self.memory[idx]
+            .as_mut()
+            .map(|page| &mut Arc::make_mut(page).data)
+        {
             for (index, value) in data.iter().enumerate() {
                 page_data[in_page_start_addr + index] = *value;
             }
@@ -79,7 +626,7 @@ impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> PagedMemory<NUM_PAGES, PAGE
             for (index, value) in data.iter().enumerate() {
                 new_page.data[in_page_start_addr + index] = *value;
             }
-            self.memory[idx] = Some(Box::new(new_page));
+            self.memory[idx] = Some(Arc::new(new_page));
         }
     }
 
@@ -92,7 +639,7 @@ impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> PagedMemory<NUM_PAGES, PAGE
     ) {
         if let Some(page_transaction_ids) = self.memory[idx]
             .as_mut()
-            .map(|page| &mut page.transaction_ids)
+            .map(|page| &mut Arc::make_mut(page).transaction_ids)
         {
             for (index, value) in transaction_ids.iter().enumerate() {
                 page_transaction_ids[in_page_start_addr + index] = *value;
@@ -103,23 +650,183 @@ impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> PagedMemory<NUM_PAGES, PAGE
     }
 }
 
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize, MemoryError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(MemoryError::Io(error.kind())),
+        }
+    }
+    Ok(filled)
+}
+
+/// Borrowing iterator returned by [`PagedMemory::iter`].
+#[derive(Debug, Clone)]
+pub struct PagedMemoryIter<'a, const NUM_PAGES: usize, const PAGE_SIZE: usize> {
+    memory: &'a PagedMemory<NUM_PAGES, PAGE_SIZE>,
+    addr: usize,
+}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> Iterator
+    for PagedMemoryIter<'_, NUM_PAGES, PAGE_SIZE>
+{
+    type Item = (usize, u8);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.addr == NUM_PAGES * PAGE_SIZE {
+            return None;
+        }
+        let addr = self.addr;
+        self.addr += 1;
+        Some((addr, self.memory.byte_at(addr)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = NUM_PAGES * PAGE_SIZE - self.addr;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> ExactSizeIterator
+    for PagedMemoryIter<'_, NUM_PAGES, PAGE_SIZE>
+{
+}
+
+/// Consuming iterator returned by [`PagedMemory::into_iter`].
+#[derive(Debug, Clone)]
+pub struct PagedMemoryIntoIter<const NUM_PAGES: usize, const PAGE_SIZE: usize> {
+    memory: PagedMemory<NUM_PAGES, PAGE_SIZE>,
+    addr: usize,
+}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> Iterator
+    for PagedMemoryIntoIter<NUM_PAGES, PAGE_SIZE>
+{
+    type Item = (usize, u8);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.addr == NUM_PAGES * PAGE_SIZE {
+            return None;
+        }
+        let addr = self.addr;
+        self.addr += 1;
+        Some((addr, self.memory.byte_at(addr)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = NUM_PAGES * PAGE_SIZE - self.addr;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> ExactSizeIterator
+    for PagedMemoryIntoIter<NUM_PAGES, PAGE_SIZE>
+{
+}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> IntoIterator
+    for PagedMemory<NUM_PAGES, PAGE_SIZE>
+{
+    type Item = (usize, u8);
+    type IntoIter = PagedMemoryIntoIter<NUM_PAGES, PAGE_SIZE>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        PagedMemoryIntoIter {
+            memory: self,
+            addr: 0,
+        }
+    }
+}
+
+impl<'a, const NUM_PAGES: usize, const PAGE_SIZE: usize> IntoIterator
+    for &'a PagedMemory<NUM_PAGES, PAGE_SIZE>
+{
+    type Item = (usize, u8);
+    type IntoIter = PagedMemoryIter<'a, NUM_PAGES, PAGE_SIZE>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Writes every `(addr, data)` pair directly, without recording transactions, like
+/// [`PagedMemory::load_bin`]. Panics if a write does not fit into the address space.
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> Extend<(usize, Vec<u8>)>
+    for PagedMemory<NUM_PAGES, PAGE_SIZE>
+{
+    fn extend<I: IntoIterator<Item = (usize, Vec<u8>)>>(&mut self, iter: I) {
+        for (addr, data) in iter {
+            if data.is_empty() {
+                continue;
+            }
+            assert!(
+                addr.checked_add(data.len())
+                    .is_some_and(|end| end <= NUM_PAGES * PAGE_SIZE),
+                "write of {} bytes at {addr:#x} does not fit into the address space",
+                data.len()
+            );
+            self.load_data(addr, &data);
+        }
+    }
+}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> Default for PagedMemory<NUM_PAGES, PAGE_SIZE> {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+// Dirty flags track accesses rather than contents and do not take part in comparisons.
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> PartialEq
+    for PagedMemory<NUM_PAGES, PAGE_SIZE>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.comparison_key() == other.comparison_key()
+    }
+}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> Eq for PagedMemory<NUM_PAGES, PAGE_SIZE> {}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> PartialOrd
+    for PagedMemory<NUM_PAGES, PAGE_SIZE>
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> Ord for PagedMemory<NUM_PAGES, PAGE_SIZE> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.comparison_key().cmp(&other.comparison_key())
+    }
+}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> Hash for PagedMemory<NUM_PAGES, PAGE_SIZE> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.comparison_key().hash(state);
+    }
+}
+
 impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> InternalMemoryOps
     for PagedMemory<NUM_PAGES, PAGE_SIZE>
 {
+    type TransactionStorage = Transaction;
+
     fn write_data(&mut self, addr: usize, data: &[u8]) {
         let size = data.len();
         assert!(size > 0);
         let in_page_addr_mask = (1 << (PAGE_SIZE.ilog2())) - 1;
-        let page_addr_shift = PAGE_SIZE.ilog2();
 
         let start_addr = addr;
-        let end_addr = addr + size - 1;
-        let start_page_addr = start_addr >> page_addr_shift;
-        let end_page_addr = end_addr >> page_addr_shift;
+        let pages = Self::pages_containing(addr, size);
+        let (start_page_addr, end_page_addr) = (*pages.start(), *pages.end());
         let in_page_start_addr = start_addr & in_page_addr_mask;
 
         if start_page_addr == end_page_addr {
-            self.write_page_data(start_page_addr, in_page_start_addr, &data);
+            self.write_page_data(start_page_addr, in_page_start_addr, data);
         } else {
             self.write_page_data(
                 start_page_addr,
@@ -139,16 +846,14 @@ impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> InternalMemoryOps
         let size = transaction_ids.len();
         assert!(size > 0);
         let in_page_addr_mask = (1 << (PAGE_SIZE.ilog2())) - 1;
-        let page_addr_shift = PAGE_SIZE.ilog2();
 
         let start_addr = addr;
-        let end_addr = addr + size - 1;
-        let start_page_addr = start_addr >> page_addr_shift;
-        let end_page_addr = end_addr >> page_addr_shift;
+        let pages = Self::pages_containing(addr, size);
+        let (start_page_addr, end_page_addr) = (*pages.start(), *pages.end());
         let in_page_start_addr = start_addr & in_page_addr_mask;
 
         if start_page_addr == end_page_addr {
-            self.write_page_transaction_ids(start_page_addr, in_page_start_addr, &transaction_ids);
+            self.write_page_transaction_ids(start_page_addr, in_page_start_addr, transaction_ids);
         } else {
             self.write_page_transaction_ids(
                 start_page_addr,
@@ -168,10 +873,41 @@ impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> InternalMemoryOps
         }
     }
 
+    fn clear_transaction_ids(&mut self) {
+        self.remap_transaction_ids(&|_| TransactionId(0));
+    }
+
+    fn remap_transaction_ids(&mut self, map: &dyn Fn(TransactionId) -> TransactionId) {
+        for (page, initialized) in self.memory.iter_mut().zip(self.initialized.iter_mut()) {
+            let Some(page) = page else {
+                continue;
+            };
+            for (offset, id) in Arc::make_mut(page).transaction_ids.iter_mut().enumerate() {
+                let new_id = map(*id);
+                if *id != TransactionId(0) && new_id == TransactionId(0) {
+                    initialized.mark(offset..offset + 1, PAGE_SIZE);
+                }
+                *id = new_id;
+            }
+        }
+    }
+
+    fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    fn transactions_mut(&mut self) -> &mut Vec<Transaction> {
+        &mut self.transactions
+    }
+
     fn transaction_vec_len(&self) -> usize {
         self.transactions.len()
     }
 
+    fn transaction_vec_capacity(&self) -> usize {
+        self.transactions.capacity()
+    }
+
     fn transaction_vec_push(&mut self, transaction: Transaction) {
         self.transactions.push(transaction);
     }
@@ -184,22 +920,83 @@ impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> InternalMemoryOps
         self.transaction_idx = idx;
     }
 
-    fn address_space_size(&self) -> usize {
-        NUM_PAGES * PAGE_SIZE
+    fn default_value(&self) -> u8 {
+        self.default_value
+    }
+
+    fn is_initialized(&self, addr: usize) -> bool {
+        self.initialized[addr / PAGE_SIZE].contains(addr % PAGE_SIZE)
+    }
+
+    // Bytes that were never written still hold the old default and follow the rebase.
+    // Unallocated pages with initialized bytes are materialized so that those keep reading
+    // the old one.
+    fn replace_default_value(&mut self, new_default: u8) {
+        let old_default = self.default_value;
+        for ((page, dirty), initialized) in self
+            .memory
+            .iter_mut()
+            .zip(self.dirty_since_clear.iter_mut())
+            .zip(self.initialized.iter())
+        {
+            if !initialized.is_empty() {
+                page.get_or_insert_with(|| Arc::new(Page::new(old_default)));
+            }
+            if *initialized == InitializedBytes::Full {
+                continue;
+            }
+            let Some(page) = page else {
+                continue;
+            };
+            let page = Arc::make_mut(page);
+            for (offset, (byte, id)) in page
+                .data
+                .iter_mut()
+                .zip(page.transaction_ids.iter())
+                .enumerate()
+            {
+                if *id == TransactionId(0) && *byte == old_default && !initialized.contains(offset)
+                {
+                    *byte = new_default;
+                    *dirty = true;
+                }
+            }
+        }
+        self.default_value = new_default;
+    }
+
+    fn add_transaction_data(
+        &mut self,
+        addr: usize,
+        data: TransactionData,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        crate::record_transaction(self, addr, data, code_location)
+    }
+
+    #[cfg(feature = "protection")]
+    fn protection(&self) -> &crate::protection::ProtectionMap {
+        &self.protection
+    }
+
+    #[cfg(feature = "protection")]
+    fn protection_mut(&mut self) -> &mut crate::protection::ProtectionMap {
+        &mut self.protection
     }
 }
 
 impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> Memory for PagedMemory<NUM_PAGES, PAGE_SIZE> {
     fn read(&self, addr: usize, size: usize) -> Vec<u8> {
         assert!(size > 0);
-        assert!(addr + size < PAGE_SIZE * NUM_PAGES);
+        assert!(addr
+            .checked_add(size)
+            .is_some_and(|end| end <= PAGE_SIZE * NUM_PAGES));
         let in_page_addr_mask = (1 << (PAGE_SIZE.ilog2())) - 1;
-        let page_addr_shift = PAGE_SIZE.ilog2();
 
         let start_addr = addr;
         let end_addr = addr + size - 1;
-        let start_page_addr = start_addr >> page_addr_shift;
-        let end_page_addr = end_addr >> page_addr_shift;
+        let pages = Self::pages_containing(addr, size);
+        let (start_page_addr, end_page_addr) = (*pages.start(), *pages.end());
         let in_page_start_addr = start_addr & in_page_addr_mask;
         let in_page_end_addr = end_addr & in_page_addr_mask;
 
@@ -216,15 +1013,48 @@ impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> Memory for PagedMemory<NUM_
         data
     }
 
+    fn visit_data(&self, addr: usize, size: usize, visitor: &mut dyn FnMut(&[u8])) {
+        assert!(addr
+            .checked_add(size)
+            .is_some_and(|end| end <= PAGE_SIZE * NUM_PAGES));
+        for (page_idx, range) in page_chunks(PAGE_SIZE, addr, size) {
+            match &self.memory[page_idx] {
+                Some(page) => visitor(&page.data[range]),
+                None => visit_default(self.default_value, range.len(), visitor),
+            }
+        }
+    }
+
+    // Unallocated pages are counted in one step instead of being visited byte by byte.
+    fn byte_frequency_histogram(&self, addr: usize, size: usize) -> [u32; 256] {
+        assert!(addr
+            .checked_add(size)
+            .is_some_and(|end| end <= PAGE_SIZE * NUM_PAGES));
+        let mut histogram = [0; 256];
+        for (page_idx, range) in page_chunks(PAGE_SIZE, addr, size) {
+            match &self.memory[page_idx] {
+                Some(page) => {
+                    for &byte in &page.data[range] {
+                        histogram[byte as usize] += 1;
+                    }
+                }
+                None => histogram[self.default_value as usize] += range.len() as u32,
+            }
+        }
+        histogram
+    }
+
     fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId> {
         assert!(size > 0);
+        assert!(addr
+            .checked_add(size)
+            .is_some_and(|end| end <= PAGE_SIZE * NUM_PAGES));
         let in_page_addr_mask = (1 << (PAGE_SIZE.ilog2())) - 1;
-        let page_addr_shift = PAGE_SIZE.ilog2();
 
         let start_addr = addr;
         let end_addr = addr + size - 1;
-        let start_page_addr = start_addr >> page_addr_shift;
-        let end_page_addr = end_addr >> page_addr_shift;
+        let pages = Self::pages_containing(addr, size);
+        let (start_page_addr, end_page_addr) = (*pages.start(), *pages.end());
         let in_page_start_addr = start_addr & in_page_addr_mask;
         let in_page_end_addr = end_addr & in_page_addr_mask;
 
@@ -253,9 +1083,14 @@ impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> Memory for PagedMemory<NUM_
     fn current_transaction_id(&self) -> usize {
         self.transaction_idx
     }
+
+    fn address_space_size(&self) -> usize {
+        NUM_PAGES * PAGE_SIZE
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::needless_range_loop, clippy::useless_vec)]
 mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
@@ -274,7 +1109,7 @@ mod tests {
                 counter += 1;
             }
             let transaction_ids = [TransactionId(1); PAGE_SIZE];
-            let page = Box::new(Page {
+            let page = Arc::new(Page {
                 data,
                 transaction_ids,
             });
@@ -287,7 +1122,7 @@ mod tests {
                 counter += 1;
             }
             let transaction_ids = [TransactionId(1); PAGE_SIZE];
-            let page = Box::new(Page {
+            let page = Arc::new(Page {
                 data,
                 transaction_ids,
             });
@@ -296,6 +1131,10 @@ mod tests {
         PagedMemory {
             default_value,
             memory,
+            dirty_since_clear: [false; NUM_PAGES],
+            initialized: std::array::from_fn(|_| InitializedBytes::Empty),
+            #[cfg(feature = "protection")]
+            protection: Default::default(),
             transactions: Vec::new(),
             transaction_idx: 0,
         }
@@ -454,4 +1293,726 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn pages() {
+        let memory = setup_test_memory::<4, 4>(0xab);
+        let pages: Vec<_> = memory.pages().collect();
+        assert_eq!(pages.len(), 4);
+        assert_eq!(pages[0].1.unwrap().data(), &[0, 1, 2, 3]);
+        assert_eq!(pages[1].1.unwrap().data(), &[4, 5, 6, 7]);
+        assert!(pages[2].1.is_none());
+        assert_eq!(pages[3].1.unwrap().data(), &[8, 9, 10, 11]);
+        assert_eq!(
+            pages[3].1.unwrap().transaction_ids(),
+            &[TransactionId(1); 4]
+        );
+        let indices: Vec<_> = pages.iter().map(|(idx, _)| *idx).collect();
+        assert_eq!(indices, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn validate_params() {
+        assert!(PagedMemory::<8, 4>::validate_params().is_ok());
+        assert_eq!(
+            PagedMemory::<8, 6>::validate_params().unwrap_err().as_str(),
+            "PAGE_SIZE must be a power of two, got 6"
+        );
+        assert_eq!(
+            PagedMemory::<0, 4>::validate_params().unwrap_err().as_str(),
+            "NUM_PAGES must be a power of two, got 0"
+        );
+    }
+
+    #[test]
+    fn write_fill() {
+        const FILL_SIZE: usize = 1 << 20;
+        let mut memory = PagedMemory::<512, 4096>::new(0xab);
+        memory.write_fill(0x1001, FILL_SIZE, 0x5a, 0x0).unwrap();
+
+        assert_eq!(memory.read(0x1000, 2), vec![0xab, 0x5a]);
+        assert!(memory
+            .read(0x1001, FILL_SIZE)
+            .iter()
+            .all(|&byte| byte == 0x5a));
+        assert_eq!(memory.read(0x1000 + FILL_SIZE, 2), vec![0x5a, 0xab]);
+        assert_eq!(memory.transactions()[0].data.len(), FILL_SIZE);
+        assert_eq!(memory.transactions()[0].data.stored_len(), 1);
+
+        let mut written = PagedMemory::<512, 4096>::new(0xab);
+        written
+            .add_transaction(0x1001, vec![0x5a; FILL_SIZE], 0x0)
+            .unwrap();
+        assert_eq!(memory.transactions(), written.transactions());
+        assert_eq!(
+            written.transaction_history_size_bytes() - memory.transaction_history_size_bytes(),
+            FILL_SIZE - 1
+        );
+
+        memory.previous_transaction().unwrap();
+        assert!(memory
+            .read(0x0, 2 * FILL_SIZE)
+            .iter()
+            .all(|&byte| byte == 0xab));
+    }
+
+    #[test]
+    fn page_transaction_id_histogram() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.add_transaction(0x5, vec![0, 1, 2], 0x0).unwrap();
+        memory.add_transaction(0x4, vec![3], 0x0).unwrap();
+        assert_eq!(
+            memory.page_transaction_id_histogram(1),
+            HashMap::from([(TransactionId(1), 3), (TransactionId(2), 1)])
+        );
+        assert_eq!(
+            memory.page_transaction_id_histogram(0),
+            HashMap::from([(TransactionId(0), 4)])
+        );
+    }
+
+    #[test]
+    fn hash() {
+        use std::collections::HashSet;
+
+        let mut first = PagedMemory::<4, 4>::new(0xab);
+        let mut second = PagedMemory::<4, 4>::new(0xab);
+        first.add_transaction(0x5, vec![0, 1, 2], 0x0).unwrap();
+        second.add_transaction(0x5, vec![0, 1, 2], 0x0).unwrap();
+
+        let mut set = HashSet::new();
+        assert!(set.insert(first.clone()));
+        assert!(!set.insert(second.clone()));
+        second.previous_transaction().unwrap();
+        assert!(set.insert(second));
+
+        let transactions: HashSet<_> = first.transactions().iter().cloned().collect();
+        assert_eq!(transactions.len(), 1);
+    }
+
+    #[test]
+    fn copy_range() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory
+            .add_transaction(0x2, vec![0, 1, 2, 3, 4], 0x0)
+            .unwrap();
+
+        memory.copy_range(0x2, 0x4, 5, 0x0).unwrap();
+        assert_eq!(
+            memory.read(0x0, 10),
+            vec![0xab, 0xab, 0, 1, 0, 1, 2, 3, 4, 0xab]
+        );
+        memory.previous_transaction().unwrap();
+        assert_eq!(
+            memory.read(0x0, 10),
+            vec![0xab, 0xab, 0, 1, 2, 3, 4, 0xab, 0xab, 0xab]
+        );
+
+        memory.next_transaction().unwrap();
+        memory.copy_range(0x4, 0x1, 5, 0x0).unwrap();
+        assert_eq!(
+            memory.read(0x0, 10),
+            vec![0xab, 0, 1, 2, 3, 4, 2, 3, 4, 0xab]
+        );
+        memory.previous_transaction().unwrap();
+        assert_eq!(
+            memory.read(0x0, 10),
+            vec![0xab, 0xab, 0, 1, 0, 1, 2, 3, 4, 0xab]
+        );
+        memory.next_transaction().unwrap();
+
+        assert_eq!(
+            memory.copy_range(0xc, 0x0, 5, 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            memory.copy_range(0x0, 0xc, 5, 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(memory.current_transaction_id(), 3);
+    }
+
+    #[test]
+    fn read_page_data_into_buffer() {
+        let memory = setup_test_memory::<4, 4>(0xab);
+        let mut buf = [0; 6];
+        assert_eq!(memory.read_page_data_into_buffer(1, &mut buf), Ok(4));
+        assert_eq!(buf, [4, 5, 6, 7, 0, 0]);
+        assert_eq!(memory.read_page_data_into_buffer(2, &mut buf), Ok(4));
+        assert_eq!(buf, [0xab, 0xab, 0xab, 0xab, 0, 0]);
+        assert_eq!(
+            memory.read_page_data_into_buffer(4, &mut buf),
+            Err(MemoryError::PageOutOfRange(4))
+        );
+        assert_eq!(
+            memory.read_page_data_into_buffer(0, &mut buf[..3]),
+            Err(MemoryError::BufferTooSmall {
+                required: 4,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn with_capacity() {
+        let memory = PagedMemory::<4, 4>::with_capacity(0xab, &[1, 3, 1]);
+        assert!(memory.memory[0].is_none());
+        assert_eq!(memory.memory[1].as_ref().unwrap().data(), &[0xab; 4]);
+        assert!(memory.memory[2].is_none());
+        assert_eq!(memory.memory[3].as_ref().unwrap().data(), &[0xab; 4]);
+        assert_eq!(memory.read(0, 16), vec![0xab; 16]);
+        assert_eq!(
+            memory.read_transaction_ids(0, 16),
+            vec![TransactionId(0); 16]
+        );
+    }
+
+    #[test]
+    fn commit_all_pages() {
+        let mut committed = PagedMemory::<4, 4>::commit_all_pages(0xab);
+        let mut lazy = PagedMemory::<4, 4>::new(0xab);
+        assert!(committed.memory.iter().all(|page| page.is_some()));
+        assert!(committed.memory_eq(&lazy));
+
+        committed.add_transaction(0x3, vec![1, 2, 3], 0x0).unwrap();
+        lazy.add_transaction(0x3, vec![1, 2, 3], 0x0).unwrap();
+        committed.add_transaction(0xe, vec![4, 5], 0x0).unwrap();
+        lazy.add_transaction(0xe, vec![4, 5], 0x0).unwrap();
+        assert_eq!(committed.read(0x0, 16), lazy.read(0x0, 16));
+        assert_eq!(
+            committed.read_transaction_ids(0x0, 16),
+            lazy.read_transaction_ids(0x0, 16)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "page index 4 is out of range")]
+    fn with_capacity_out_of_range() {
+        let _ = PagedMemory::<4, 4>::with_capacity(0xab, &[4]);
+    }
+
+    #[test]
+    fn clone_on_write() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.add_transaction(0x2, vec![1, 2, 3, 4], 0x0).unwrap();
+        let mut clone = memory.clone();
+        for (page, cloned_page) in memory.memory.iter().zip(clone.memory.iter()) {
+            if let (Some(page), Some(cloned_page)) = (page, cloned_page) {
+                assert!(Arc::ptr_eq(page, cloned_page));
+            }
+        }
+
+        clone.add_transaction(0x4, vec![5], 0x0).unwrap();
+        assert!(Arc::ptr_eq(
+            memory.memory[0].as_ref().unwrap(),
+            clone.memory[0].as_ref().unwrap()
+        ));
+        assert!(!Arc::ptr_eq(
+            memory.memory[1].as_ref().unwrap(),
+            clone.memory[1].as_ref().unwrap()
+        ));
+        assert_eq!(
+            memory.read(0x0, 8),
+            vec![0xab, 0xab, 1, 2, 3, 4, 0xab, 0xab]
+        );
+        assert_eq!(clone.read(0x0, 8), vec![0xab, 0xab, 1, 2, 5, 4, 0xab, 0xab]);
+
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.read(0x0, 8), vec![0xab; 8]);
+        assert_eq!(clone.read(0x0, 8), vec![0xab, 0xab, 1, 2, 5, 4, 0xab, 0xab]);
+        assert_eq!(
+            clone.read_transaction_ids(0x2, 4),
+            vec![
+                TransactionId(1),
+                TransactionId(1),
+                TransactionId(2),
+                TransactionId(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn iter_dirty_pages() {
+        let mut memory = PagedMemory::<4, 4>::with_capacity(0xab, &[3]);
+        assert_eq!(memory.iter_dirty_pages().collect::<Vec<_>>(), vec![]);
+
+        memory.add_transaction(0x2, vec![1, 2, 3], 0x0).unwrap();
+        assert_eq!(memory.iter_dirty_pages().collect::<Vec<_>>(), vec![0, 1]);
+
+        memory.clear_dirty_flags();
+        assert_eq!(memory.iter_dirty_pages().collect::<Vec<_>>(), vec![]);
+        assert_eq!(memory.read(0x2, 3), vec![1, 2, 3]);
+
+        memory.add_transaction(0x9, vec![4], 0x0).unwrap();
+        assert_eq!(memory.iter_dirty_pages().collect::<Vec<_>>(), vec![2]);
+
+        memory.clear_dirty_flags();
+        memory.previous_transaction().unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.iter_dirty_pages().collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn dirty_flags_do_not_affect_comparisons() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let mut a = PagedMemory::<4, 4>::from_flat_bytes(&[1; 16], 0xab).unwrap();
+        a.add_transaction(0x5, vec![4], 0x0).unwrap();
+        let mut b = a.clone();
+        b.clear_dirty_flags();
+        assert_ne!(
+            a.iter_dirty_pages().collect::<Vec<_>>(),
+            b.iter_dirty_pages().collect::<Vec<_>>()
+        );
+
+        assert_eq!(a, b);
+        assert_eq!(a.cmp(&b), Ordering::Equal);
+        let hash = |memory: &PagedMemory<4, 4>| {
+            let mut hasher = DefaultHasher::new();
+            memory.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&a), hash(&b));
+
+        b.add_transaction(0x6, vec![5], 0x0).unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn into_page_size() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.add_transaction(0x2, vec![1, 2, 3], 0x0).unwrap();
+        memory.clear_dirty_flags();
+        memory.add_transaction(0x9, vec![4], 0x0).unwrap();
+        let data = memory.read(0x0, 16);
+        let ids = memory.read_transaction_ids(0x0, 16);
+
+        let mut larger = memory.clone().into_page_size::<2, 8>().unwrap();
+        assert_eq!(larger.read(0x0, 16), data);
+        assert_eq!(larger.read_transaction_ids(0x0, 16), ids);
+        assert_eq!(larger.iter_dirty_pages().collect::<Vec<_>>(), vec![1]);
+        assert!(larger.memory.iter().all(Option::is_some));
+
+        let mut smaller = memory.into_page_size::<8, 2>().unwrap();
+        assert_eq!(smaller.read(0x0, 16), data);
+        assert_eq!(smaller.read_transaction_ids(0x0, 16), ids);
+        assert_eq!(smaller.iter_dirty_pages().collect::<Vec<_>>(), vec![4, 5]);
+        assert!(smaller.memory[5].is_some());
+        assert!(smaller.memory[6].is_none());
+
+        larger.move_to_transaction(TransactionId(0)).unwrap();
+        smaller.move_to_transaction(TransactionId(0)).unwrap();
+        assert_eq!(larger.read(0x0, 16), vec![0xab; 16]);
+        assert_eq!(smaller.read(0x0, 16), vec![0xab; 16]);
+
+        assert_eq!(
+            PagedMemory::<4, 4>::new(0xab).into_page_size::<4, 8>(),
+            Err(MemoryError::SizeMismatch {
+                expected: 16,
+                actual: 32
+            })
+        );
+    }
+
+    #[test]
+    fn default() {
+        let memory = PagedMemory::<4, 4>::default();
+        assert_eq!(memory.read(0x0, 16), vec![0; 16]);
+        assert_eq!(memory.transaction_vec_len(), 0);
+    }
+
+    #[test]
+    fn load_bin() {
+        let image: Vec<u8> = (1..=7).collect();
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.load_bin(image.as_slice(), 0x3).unwrap();
+        let mut expected = vec![0xab; 16];
+        expected[0x3..0xa].copy_from_slice(&image);
+        assert_eq!(memory.read(0x0, 16), expected);
+        assert_eq!(
+            memory.read_transaction_ids(0x0, 16),
+            vec![TransactionId(0); 16]
+        );
+        assert_eq!(memory.transaction_vec_len(), 0);
+        assert!(memory.memory[3].is_none());
+
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.load_bin(image.as_slice(), 0x9).unwrap();
+        assert_eq!(memory.read(0x9, 7), image);
+        assert_eq!(
+            memory.load_bin(image.as_slice(), 0xa),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            memory.load_bin(image.as_slice(), 0x11),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        memory.load_bin([].as_slice(), 0x10).unwrap();
+
+        struct FailingReader;
+        impl Read for FailingReader {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::PermissionDenied.into())
+            }
+        }
+        assert_eq!(
+            memory.load_bin(FailingReader, 0x0),
+            Err(MemoryError::Io(io::ErrorKind::PermissionDenied))
+        );
+    }
+
+    #[cfg(feature = "elf")]
+    #[test]
+    fn load_elf() {
+        // ELF64 little-endian header followed by two program headers: a PT_LOAD segment
+        // with 3 file bytes and 5 memory bytes at 0x6, and a PT_NOTE that must be skipped.
+        let mut image = vec![0; 0x40 + 2 * 0x38];
+        image[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        image[0x20..0x28].copy_from_slice(&0x40u64.to_le_bytes());
+        image[0x36..0x38].copy_from_slice(&0x38u16.to_le_bytes());
+        image[0x38..0x3a].copy_from_slice(&2u16.to_le_bytes());
+        let data_offset = image.len() as u64;
+        let load = &mut image[0x40..0x78];
+        load[0x00..0x04].copy_from_slice(&1u32.to_le_bytes());
+        load[0x08..0x10].copy_from_slice(&data_offset.to_le_bytes());
+        load[0x18..0x20].copy_from_slice(&0x6u64.to_le_bytes());
+        load[0x20..0x28].copy_from_slice(&3u64.to_le_bytes());
+        load[0x28..0x30].copy_from_slice(&5u64.to_le_bytes());
+        image[0x78..0x7c].copy_from_slice(&4u32.to_le_bytes());
+        image.extend_from_slice(&[1, 2, 3]);
+
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.load_elf(&image).unwrap();
+        let mut expected = vec![0xab; 16];
+        expected[0x6..0xb].copy_from_slice(&[1, 2, 3, 0, 0]);
+        assert_eq!(memory.read(0x0, 16), expected);
+        assert_eq!(memory.transaction_vec_len(), 0);
+
+        let mut small = PagedMemory::<2, 4>::new(0xab);
+        assert_eq!(small.load_elf(&image), Err(MemoryError::AddressOutOfRange));
+        assert_eq!(small.read(0x0, 8), vec![0xab; 8]);
+        assert_eq!(
+            small.load_elf(&image[..0x50]),
+            Err(MemoryError::InvalidElf("image is truncated"))
+        );
+        assert_eq!(
+            small.load_elf(b"not an elf"),
+            Err(MemoryError::InvalidElf("missing ELF magic"))
+        );
+    }
+
+    #[test]
+    fn upcast_and_downcast() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.add_transaction(0x2, vec![1, 2, 3], 0x0).unwrap();
+        memory.add_transaction(0xe, vec![4, 5], 0x0).unwrap();
+        memory.previous_transaction().unwrap();
+        let original = memory.clone();
+
+        let mut upcast: PagedMemory<8, 4> = memory.upcast();
+        assert_eq!(upcast.read(0x0, 16), original.read(0x0, 16));
+        assert_eq!(upcast.read(0x10, 16), vec![0xab; 16]);
+        assert!(upcast.memory[4..].iter().all(|page| page.is_none()));
+        assert_eq!(upcast.transactions(), original.transactions());
+        upcast.next_transaction().unwrap();
+        assert_eq!(upcast.read(0xe, 2), vec![4, 5]);
+        upcast.previous_transaction().unwrap();
+
+        let mut downcast: PagedMemory<4, 4> = upcast.clone().try_downcast().unwrap();
+        assert_eq!(downcast, original);
+        downcast.next_transaction().unwrap();
+        assert_eq!(downcast.read(0xe, 2), vec![4, 5]);
+
+        upcast.next_transaction().unwrap();
+        upcast.add_transaction(0x10, vec![6], 0x0).unwrap();
+        upcast.previous_transaction().unwrap();
+        let upcast = upcast.try_downcast::<4>().unwrap_err();
+        assert_eq!(upcast.current_transaction_id(), 2);
+
+        let mut upcast = PagedMemory::<8, 4>::with_capacity(0xab, &[6]);
+        assert!(upcast.clone().try_downcast::<4>().is_ok());
+        upcast.write_data(0x18, &[0x0]);
+        assert!(upcast.try_downcast::<4>().is_err());
+    }
+
+    #[test]
+    fn swap_pages() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.add_transaction(0x2, vec![1, 2, 3], 0x0).unwrap();
+        let before = memory.clone();
+
+        memory.swap_pages(0, 3, 0x40).unwrap();
+        assert_eq!(memory.read(0x0, 4), vec![0xab; 4]);
+        assert_eq!(memory.read(0x4, 4), vec![3, 0xab, 0xab, 0xab]);
+        assert_eq!(memory.read(0xc, 4), vec![0xab, 0xab, 1, 2]);
+        assert_eq!(
+            memory.read_transaction_ids(0x0, 4),
+            vec![TransactionId(2); 4]
+        );
+        assert_eq!(
+            memory.read_transaction_ids(0xc, 4),
+            vec![TransactionId(3); 4]
+        );
+        assert_eq!(memory.current_transaction_id(), 3);
+        assert_eq!(memory.current_transaction().unwrap().code_location, 0x40);
+        assert_eq!(memory.validate_integrity(), Ok(()));
+
+        memory.previous_transaction().unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.read(0x0, 16), before.read(0x0, 16));
+        assert_eq!(
+            memory.read_transaction_ids(0x0, 16),
+            before.read_transaction_ids(0x0, 16)
+        );
+        assert_eq!(
+            memory.swap_pages(0, 1, 0x40),
+            Err(MemoryError::NotAtLatestTransaction)
+        );
+        memory.move_to_transaction(TransactionId(3)).unwrap();
+        assert_eq!(memory.read(0xc, 4), vec![0xab, 0xab, 1, 2]);
+
+        assert_eq!(
+            memory.swap_pages(1, 4, 0x40),
+            Err(MemoryError::PageOutOfRange(4))
+        );
+        memory.swap_pages(2, 2, 0x40).unwrap();
+        assert_eq!(memory.current_transaction_id(), 3);
+    }
+
+    #[test]
+    fn iter() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.add_transaction(0x3, vec![1, 2], 0x0).unwrap();
+        memory.add_transaction(0xf, vec![3], 0x0).unwrap();
+        let expected: Vec<_> = memory.read(0x0, 16).into_iter().enumerate().collect();
+
+        let iter = memory.iter();
+        assert_eq!(iter.len(), 16);
+        assert_eq!(iter.collect::<Vec<_>>(), expected);
+        let mut iter = (&memory).into_iter();
+        iter.nth(9);
+        assert_eq!(iter.len(), 6);
+
+        let mut into_iter = memory.into_iter();
+        assert_eq!(into_iter.len(), 16);
+        assert_eq!(into_iter.next(), Some((0x0, 0xab)));
+        assert_eq!(into_iter.len(), 15);
+        assert_eq!(into_iter.collect::<Vec<_>>(), expected[1..]);
+    }
+
+    #[test]
+    fn write_page_bulk() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        let mut expected = PagedMemory::<4, 4>::new(0xab);
+        memory.add_transaction(0x6, vec![1, 2, 3], 0x0).unwrap();
+        expected.add_transaction(0x6, vec![1, 2, 3], 0x0).unwrap();
+
+        memory.write_page_bulk(1, &[4, 5, 6, 7], 0x20).unwrap();
+        expected
+            .add_transaction(0x4, vec![4, 5, 6, 7], 0x20)
+            .unwrap();
+        memory.write_page_bulk(3, &[8; 4], 0x24).unwrap();
+        expected.add_transaction(0xc, vec![8; 4], 0x24).unwrap();
+        assert_eq!(memory, expected);
+
+        memory.previous_transaction().unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(
+            memory.read(0x0, 16),
+            expected
+                .read_at_transaction(0x0, 16, TransactionId(1))
+                .unwrap()
+        );
+        assert_eq!(
+            memory.write_page_bulk(0, &[0; 4], 0x0),
+            Err(MemoryError::NotAtLatestTransaction)
+        );
+        memory.move_to_transaction(TransactionId(3)).unwrap();
+        assert_eq!(
+            memory.write_page_bulk(4, &[0; 4], 0x0),
+            Err(MemoryError::PageOutOfRange(4))
+        );
+    }
+
+    #[test]
+    fn extend() {
+        let writes = vec![(0x2, vec![1, 2, 3]), (0x9, vec![]), (0xe, vec![4, 5])];
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.extend(writes.clone());
+        let mut transactional = PagedMemory::<4, 4>::new(0xab);
+        assert_eq!(
+            transactional.extend_transactional(writes.clone(), 0x0),
+            Err(MemoryError::EmptyTransaction)
+        );
+        assert_eq!(transactional.transaction_vec_len(), 1);
+        transactional
+            .extend_transactional(writes.into_iter().skip(2), 0x0)
+            .unwrap();
+
+        let expected = vec![
+            0xab, 0xab, 1, 2, 3, 0xab, 0xab, 0xab, 0xab, 0xab, 0xab, 0xab, 0xab, 0xab, 4, 5,
+        ];
+        assert_eq!(memory.read(0x0, 16), expected);
+        assert_eq!(memory.transaction_vec_len(), 0);
+        assert_eq!(
+            memory.read_transaction_ids(0x0, 16),
+            vec![TransactionId(0); 16]
+        );
+        assert_eq!(transactional.read(0x0, 16), expected);
+        assert_eq!(transactional.transaction_vec_len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit into the address space")]
+    fn extend_out_of_range() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.extend([(0xf, vec![1, 2])]);
+    }
+
+    #[test]
+    fn pages_containing() {
+        type Memory = PagedMemory<4, 4>;
+        assert_eq!(Memory::pages_containing(0x0, 1), 0..=0);
+        assert_eq!(Memory::pages_containing(0x0, 4), 0..=0);
+        assert_eq!(Memory::pages_containing(0x3, 1), 0..=0);
+        assert_eq!(Memory::pages_containing(0x3, 2), 0..=1);
+        assert_eq!(Memory::pages_containing(0x4, 4), 1..=1);
+        assert_eq!(Memory::pages_containing(0x4, 5), 1..=2);
+        assert_eq!(Memory::pages_containing(0x2, 12), 0..=3);
+        assert_eq!(Memory::pages_containing(0x0, 16), 0..=3);
+    }
+
+    #[test]
+    fn read_last_byte() {
+        let memory = PagedMemory::<4, 4>::new(0xab);
+        assert_eq!(memory.read(0xf, 1), vec![0xab]);
+        assert_eq!(memory.read(0xc, 4), vec![0xab; 4]);
+        assert_eq!(memory.read(0x0, 16), vec![0xab; 16]);
+        assert_eq!(memory.read_transaction_ids(0xf, 1), vec![TransactionId(0)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn read_past_last_byte() {
+        let memory = PagedMemory::<4, 4>::new(0xab);
+        let _ = memory.read(0xf, 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn read_transaction_ids_past_last_byte() {
+        let memory = PagedMemory::<4, 4>::new(0xab);
+        let _ = memory.read_transaction_ids(0xf, 2);
+    }
+
+    #[test]
+    fn memory_checksum() {
+        let mut memory = PagedMemory::<4, 256>::with_capacity(0x0, &[2]);
+        memory
+            .add_transaction(0x1fe, b"123456789".to_vec(), 0x0)
+            .unwrap();
+        assert_eq!(memory.memory_checksum(0x1fe, 9), 0xcbf4_3926);
+        assert_eq!(memory.memory_checksum(0x0, 0), 0);
+
+        let flat = memory.read(0x0, 1024);
+        let mut crc = crate::checksum::Crc32::new();
+        crc.update(&flat);
+        assert_eq!(memory.memory_checksum(0x0, 1024), crc.finish());
+
+        assert_eq!(
+            memory.checksum_at_transaction(0x1fe, 9, TransactionId(1)),
+            Ok(0xcbf4_3926)
+        );
+        let mut crc = crate::checksum::Crc32::new();
+        crc.update(&[0x0; 9]);
+        assert_eq!(
+            memory.checksum_at_transaction(0x1fe, 9, TransactionId(0)),
+            Ok(crc.finish())
+        );
+        assert_eq!(
+            memory.checksum_at_transaction(0x1fe, 9, TransactionId(2)),
+            Err(MemoryError::InvalidTransactionId(TransactionId(2)))
+        );
+    }
+
+    #[test]
+    fn content_cmp() {
+        let mut a = PagedMemory::<4, 4>::new(0xab);
+        let mut b = PagedMemory::<4, 4>::new(0x00);
+        assert_eq!(a.content_cmp(&b), Ordering::Greater);
+
+        b.add_transaction(0x0, vec![0xab; 16], 0x0).unwrap();
+        assert_eq!(a.content_cmp(&b), Ordering::Equal);
+        assert!(a > b);
+
+        a.add_transaction(0x9, vec![0xac], 0x0).unwrap();
+        b.add_transaction(0x2, vec![0xaa], 0x0).unwrap();
+        assert_eq!(a.content_cmp(&b), Ordering::Greater);
+        assert_eq!(b.content_cmp(&a), Ordering::Less);
+        assert_eq!(a.content_cmp(&b), a.to_flat_bytes().cmp(&b.to_flat_bytes()));
+    }
+
+    #[test]
+    fn write_page_data_aligned() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.add_transaction(0x5, vec![1], 0x0).unwrap();
+        memory
+            .write_page_data_aligned(1, Box::new([4, 5, 6, 7]), 0x30)
+            .unwrap();
+        memory
+            .write_page_data_aligned(2, Box::new([8; 4]), 0x34)
+            .unwrap();
+        assert_eq!(memory.read(0x4, 8), vec![4, 5, 6, 7, 8, 8, 8, 8]);
+        assert_eq!(memory.read_transaction_ids(0x4, 1), vec![TransactionId(2)]);
+        assert_eq!(
+            memory.transactions[1].previous_data(),
+            &[0xab, 1, 0xab, 0xab]
+        );
+        assert_eq!(memory.transactions[2].code_location, 0x34);
+        assert_eq!(
+            memory.write_page_data_aligned(4, Box::new([0; 4]), 0x0),
+            Err(MemoryError::PageOutOfRange(4))
+        );
+
+        memory.previous_transaction().unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(
+            memory.read(0x4, 8),
+            vec![0xab, 1, 0xab, 0xab, 0xab, 0xab, 0xab, 0xab]
+        );
+        assert_eq!(
+            memory.write_page_data_aligned(0, Box::new([0; 4]), 0x0),
+            Err(MemoryError::NotAtLatestTransaction)
+        );
+    }
+
+    #[test]
+    fn trim_tail() {
+        let mut memory = PagedMemory::<8, 4>::with_capacity(0xab, &[5, 7]);
+        memory.add_transaction(0x6, vec![1, 2, 3], 0x0).unwrap();
+        memory.add_transaction(0xd, vec![4], 0x0).unwrap();
+        assert_eq!(memory.trim_tail(), 4);
+        assert!(memory.memory[5].is_none());
+        assert!(memory.memory[7].is_none());
+        assert!(memory.memory[3].is_some());
+
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.trim_tail(), 5);
+        assert!(memory.memory[3].is_none());
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.trim_tail(), 8);
+        assert_eq!(memory.read(0x0, 32), vec![0xab; 32]);
+
+        memory.move_to_transaction(TransactionId(2)).unwrap();
+        assert_eq!(memory.read(0x6, 3), vec![1, 2, 3]);
+        assert_eq!(memory.read(0xd, 1), vec![4]);
+        assert_eq!(memory.trim_tail(), 4);
+    }
+
+    const _: () = {
+        const fn assert_send<T: Send>() {}
+        const fn assert_sync<T: Sync>() {}
+        assert_send::<PagedMemory<4, 4>>();
+        assert_sync::<PagedMemory<4, 4>>();
+    };
 }
@@ -0,0 +1,118 @@
+use super::PagedMemory;
+use crate::MemoryError;
+
+/// Builds a [`PagedMemory`] whose initial state is a loaded image.
+///
+/// Loaded data does not create transactions, so it is what undoing all history returns to.
+#[derive(Debug, Clone, Default)]
+pub struct PagedMemoryBuilder<const NUM_PAGES: usize, const PAGE_SIZE: usize> {
+    default_value: u8,
+    loads: Vec<(usize, Vec<u8>)>,
+}
+
+impl<const NUM_PAGES: usize, const PAGE_SIZE: usize> PagedMemoryBuilder<NUM_PAGES, PAGE_SIZE> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn default_value(&mut self, default_value: u8) -> &mut Self {
+        self.default_value = default_value;
+        self
+    }
+
+    pub fn load(&mut self, addr: usize, data: &[u8]) -> &mut Self {
+        self.loads.push((addr, data.to_vec()));
+        self
+    }
+
+    pub fn build(&self) -> Result<PagedMemory<NUM_PAGES, PAGE_SIZE>, MemoryError> {
+        let mut loads: Vec<_> = self
+            .loads
+            .iter()
+            .filter(|(_, data)| !data.is_empty())
+            .collect();
+        loads.sort_by_key(|(addr, _)| *addr);
+        for (addr, data) in loads.iter() {
+            match addr.checked_add(data.len()) {
+                Some(end) if end <= NUM_PAGES * PAGE_SIZE => {}
+                _ => return Err(MemoryError::AddressOutOfRange),
+            }
+        }
+        for pair in loads.windows(2) {
+            let (first, first_data) = pair[0];
+            let (second, _) = pair[1];
+            if first + first_data.len() > *second {
+                return Err(MemoryError::OverlappingLoad {
+                    first: *first,
+                    second: *second,
+                });
+            }
+        }
+
+        let mut memory = PagedMemory::new(self.default_value);
+        for (addr, data) in loads {
+            memory.load_data(*addr, data);
+        }
+        Ok(memory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_memory_ops::InternalMemoryOps;
+    use crate::{Memory, TransactionId};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn build() {
+        let mut memory = PagedMemoryBuilder::<4, 4>::new()
+            .default_value(0xab)
+            .load(0x6, &[1, 2, 3])
+            .load(0x0, &[4, 5])
+            .load(0x9, &[6])
+            .build()
+            .unwrap();
+
+        let expected = [
+            4, 5, 0xab, 0xab, 0xab, 0xab, 1, 2, 3, 6, 0xab, 0xab, 0xab, 0xab, 0xab, 0xab,
+        ];
+        assert_eq!(memory.read(0, 16), expected);
+        assert_eq!(
+            memory.read_transaction_ids(0, 16),
+            vec![TransactionId(0); 16]
+        );
+        assert_eq!(memory.transaction_vec_len(), 0);
+        assert!(memory.pages().nth(3).unwrap().1.is_none());
+
+        memory.add_transaction(0x7, vec![0x42], 0x0).unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.read(0, 16), expected);
+    }
+
+    #[test]
+    fn build_errors() {
+        assert_eq!(
+            PagedMemoryBuilder::<4, 4>::new()
+                .load(0x4, &[1, 2, 3])
+                .load(0x2, &[4, 5, 6])
+                .build(),
+            Err(MemoryError::OverlappingLoad {
+                first: 0x2,
+                second: 0x4
+            })
+        );
+        assert_eq!(
+            PagedMemoryBuilder::<4, 4>::new()
+                .load(0xe, &[1, 2, 3])
+                .build(),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            PagedMemoryBuilder::<4, 4>::new()
+                .load(usize::MAX, &[1])
+                .build(),
+            Err(MemoryError::AddressOutOfRange)
+        );
+    }
+}
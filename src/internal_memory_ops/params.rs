@@ -0,0 +1,78 @@
+use std::fmt;
+
+const MESSAGE_CAPACITY: usize = 96;
+
+pub struct ParamsError {
+    message: [u8; MESSAGE_CAPACITY],
+    len: usize,
+}
+
+impl ParamsError {
+    pub const fn new(text: &str) -> Self {
+        let mut error = Self {
+            message: [0; MESSAGE_CAPACITY],
+            len: 0,
+        };
+        error.push_str(text);
+        error
+    }
+
+    const fn push_str(&mut self, text: &str) {
+        let bytes = text.as_bytes();
+        let mut idx = 0;
+        while idx < bytes.len() {
+            self.message[self.len] = bytes[idx];
+            self.len += 1;
+            idx += 1;
+        }
+    }
+
+    pub const fn with_value(mut self, value: usize) -> Self {
+        self.push_str(", got ");
+        let mut digits = [0; 20];
+        let mut count = 0;
+        let mut rest = value;
+        loop {
+            digits[count] = b'0' + (rest % 10) as u8;
+            count += 1;
+            rest /= 10;
+            if rest == 0 {
+                break;
+            }
+        }
+        while count > 0 {
+            count -= 1;
+            self.message[self.len] = digits[count];
+            self.len += 1;
+        }
+        self
+    }
+
+    pub const fn as_str(&self) -> &str {
+        match std::str::from_utf8(self.message.split_at(self.len).0) {
+            Ok(message) => message,
+            Err(_) => panic!("parameter error message is not valid UTF-8"),
+        }
+    }
+}
+
+impl fmt::Debug for ParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+pub const fn validate_paging_params(num_pages: usize, page_size: usize) -> Result<(), ParamsError> {
+    if !page_size.is_power_of_two() {
+        return Err(ParamsError::new("PAGE_SIZE must be a power of two").with_value(page_size));
+    }
+    if !num_pages.is_power_of_two() {
+        return Err(ParamsError::new("NUM_PAGES must be a power of two").with_value(num_pages));
+    }
+    if num_pages.checked_mul(page_size).is_none() {
+        return Err(ParamsError::new(
+            "NUM_PAGES * PAGE_SIZE must fit into the address space",
+        ));
+    }
+    Ok(())
+}
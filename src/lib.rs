@@ -1,85 +1,445 @@
+//! Memory models with a reversible transaction log, for use in emulators and debuggers.
+//!
+//! # Endianness
+//!
+//! Memories store bytes exactly in the order they are passed to
+//! [`Memory::add_transaction`]; no byte swapping happens anywhere in the crate. Typed helpers
+//! spell out the byte order in their name with an `_le` (little-endian) or `_be`
+//! (big-endian) suffix, e.g. [`Memory::write_u32_le`] and [`Memory::write_u32_be`].
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use internal_memory_ops::TransactionData;
+
+mod arena;
+mod backend;
+mod checksum;
+#[cfg(feature = "elf")]
+mod elf;
+mod error;
+mod events;
 mod internal_memory_ops;
-pub use internal_memory_ops::PagedMemory;
-use internal_memory_ops::Transaction;
+#[cfg(feature = "protection")]
+mod protection;
+mod snapshot;
+mod timestamp;
+#[cfg(feature = "trace")]
+mod trace;
+mod transaction_log;
+mod transaction_storage;
+mod watcher;
+mod writer;
+pub use arena::MemoryArena;
+pub use backend::{BackendMemory, MemoryBackend};
+pub use error::{IntegrityError, MemoryError};
+pub use events::{EventQueue, MemoryEvent, MemoryEventSource, StreamingMemory};
+pub use internal_memory_ops::{
+    intersect_transaction_ranges, GrowableMemory, Page, PagedMemory, PagedMemoryBuilder,
+    PagedMemoryIntoIter, PagedMemoryIter, Transaction,
+};
+pub use snapshot::{MemoryDiff, MemorySnapshot};
+pub use timestamp::{CycleCount, Timestamp, TimestampedMemory, TimestampedTransaction};
+#[cfg(feature = "trace")]
+pub use trace::TracingMemory;
+pub use transaction_log::TransactionLog;
+pub use transaction_storage::{SmallTransaction, TransactionStorage};
+pub use watcher::{MemoryWatcher, WatchId};
+pub use writer::MemoryWriter;
 
 #[cfg(feature = "naive")]
 pub use internal_memory_ops::NaiveMemory;
 
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Copy)]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Copy, Hash)]
 #[repr(transparent)]
 pub struct TransactionId(u32);
 
+impl TransactionId {
+    /// The state before any transaction was applied.
+    pub const INITIAL: TransactionId = TransactionId(0);
+    /// Sentinel for "the newest possible state"; not necessarily a valid index into the log.
+    pub const MAX: TransactionId = TransactionId(u32::MAX);
+}
+
+impl From<u32> for TransactionId {
+    fn from(id: u32) -> Self {
+        Self(id)
+    }
+}
+
+impl From<TransactionId> for u32 {
+    fn from(id: TransactionId) -> Self {
+        id.0
+    }
+}
+
+impl TryFrom<usize> for TransactionId {
+    type Error = ();
+
+    fn try_from(id: usize) -> Result<Self, Self::Error> {
+        u32::try_from(id).map(Self).map_err(|_| ())
+    }
+}
+
+impl fmt::Display for TransactionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Debug for TransactionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TransactionId({:#010x})", self.0)
+    }
+}
+
+impl fmt::LowerHex for TransactionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl fmt::UpperHex for TransactionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Copy, Hash)]
+pub struct Checkpoint(TransactionId);
+
 pub trait Memory: internal_memory_ops::InternalMemoryOps {
     fn read(&self, addr: usize, size: usize) -> Vec<u8>;
     fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId>;
     fn current_transaction_id(&self) -> usize;
+    /// Total number of addressable bytes.
+    #[must_use]
+    fn address_space_size(&self) -> usize;
 
-    fn next_transaction(&mut self) -> Result<(), ()> {
+    #[must_use = "there may be no transaction to redo"]
+    fn next_transaction(&mut self) -> Result<(), MemoryError> {
         let current_idx = self.current_transaction_id();
         let Some(original_transaction) = self.get_mut_transaction(current_idx) else {
-            return Err(());
+            return Err(MemoryError::NoNextTransaction);
         };
         let transaction_idx = TransactionId((current_idx + 1) as u32);
-        let transaction = std::mem::take(original_transaction);
-        self.write_data(transaction.addr, &transaction.data);
-        self.write_transaction_ids(
+        let entry = std::mem::take(original_transaction);
+        let transaction = entry.load();
+        let ids = vec![transaction_idx; CHUNK_SIZE.min(transaction.data.len())];
+        transaction.data.visit_chunks(&mut |offset, chunk| {
+            self.write_data(transaction.addr + offset, chunk);
+            self.write_transaction_ids(transaction.addr + offset, &ids[..chunk.len()]);
+        });
+        #[cfg(feature = "verbose_debug")]
+        eprintln!(
+            "REDO addr={:#x} len={} id={transaction_idx}",
             transaction.addr,
-            &vec![transaction_idx; transaction.data.len()],
+            transaction.data.len()
         );
-        let original_transaction = self.get_mut_transaction(current_idx).unwrap();
-        let _ = std::mem::replace(original_transaction, transaction);
+        *self.get_mut_transaction(current_idx).unwrap() = entry;
         self.set_transaction_idx(current_idx + 1);
         Ok(())
     }
 
-    fn previous_transaction(&mut self) -> Result<(), ()> {
+    #[must_use = "there may be no transaction to undo"]
+    fn previous_transaction(&mut self) -> Result<(), MemoryError> {
         let current_idx = self.current_transaction_id();
         if current_idx == 0 {
-            return Err(());
+            return Err(MemoryError::NoPreviousTransaction);
         }
         let Some(original_transaction) = self.get_mut_transaction(current_idx - 1) else {
-            return Err(());
+            return Err(MemoryError::NoPreviousTransaction);
         };
-        let transaction = std::mem::take(original_transaction);
+        let entry = std::mem::take(original_transaction);
+        let transaction = entry.load();
         self.write_data(transaction.addr, &transaction.old_data);
         self.write_transaction_ids(transaction.addr, &transaction.old_ids);
-        let original_transaction = self.get_mut_transaction(current_idx - 1).unwrap();
-        let _ = std::mem::replace(original_transaction, transaction);
+        #[cfg(feature = "verbose_debug")]
+        eprintln!(
+            "UNDO addr={:#x} len={} id={current_idx}",
+            transaction.addr,
+            transaction.data.len()
+        );
+        *self.get_mut_transaction(current_idx - 1).unwrap() = entry;
         self.set_transaction_idx(current_idx - 1);
         Ok(())
     }
 
+    #[must_use = "the write is rejected when it is empty, out of range or not at the latest transaction"]
     fn add_transaction(
         &mut self,
         addr: usize,
         data: Vec<u8>,
         code_location: usize,
-    ) -> Result<(), ()> {
-        if self.transaction_vec_len() != self.current_transaction_id() {
-            return Err(());
+    ) -> Result<(), MemoryError> {
+        self.add_transaction_data(addr, data.into(), code_location)
+    }
+
+    /// Checks every precondition of [`Memory::add_transaction`] without modifying memory.
+    fn validate_transaction(&self, addr: usize, data: &[u8]) -> Result<(), MemoryError> {
+        validate_write(self, addr, data.len())
+    }
+
+    #[cfg(feature = "protection")]
+    fn set_readonly(&mut self, addr: usize, size: usize) -> Result<(), MemoryError> {
+        let address_space_size = self.address_space_size();
+        match addr.checked_add(size) {
+            Some(end) if end <= address_space_size => {
+                self.protection_mut()
+                    .set_readonly(addr..end, address_space_size);
+                Ok(())
+            }
+            _ => Err(MemoryError::AddressOutOfRange),
         }
-        if addr + data.len() >= self.address_space_size() {
-            return Err(());
+    }
+
+    #[cfg(feature = "protection")]
+    fn set_writable(&mut self, addr: usize, size: usize) -> Result<(), MemoryError> {
+        match addr.checked_add(size) {
+            Some(end) if end <= self.address_space_size() => {
+                self.protection_mut().set_writable(addr..end);
+                Ok(())
+            }
+            _ => Err(MemoryError::AddressOutOfRange),
         }
-        let old_data = self.read(addr, data.len());
-        let old_ids = self.read_transaction_ids(addr, data.len());
-        let transaction = Transaction {
+    }
+
+    #[cfg(feature = "protection")]
+    fn is_writable(&self, addr: usize) -> bool {
+        !self.protection().is_readonly(addr)
+    }
+
+    fn add_transaction_from_iterator(
+        &mut self,
+        addr: usize,
+        data: impl ExactSizeIterator<Item = u8>,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        validate_write(self, addr, data.len())?;
+        self.add_transaction(addr, data.collect(), code_location)
+    }
+
+    fn write_fill(
+        &mut self,
+        addr: usize,
+        len: usize,
+        value: u8,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        self.add_transaction_data(
             addr,
-            data,
-            old_ids,
-            old_data,
+            TransactionData::repeated(&[value], len),
             code_location,
+        )
+    }
+
+    /// Writes `value` in little-endian byte order. `addr` does not have to be aligned.
+    fn write_u32_le(
+        &mut self,
+        addr: usize,
+        value: u32,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        self.add_transaction(addr, value.to_le_bytes().to_vec(), code_location)
+    }
+
+    /// Writes `value` in big-endian byte order. `addr` does not have to be aligned.
+    fn write_u32_be(
+        &mut self,
+        addr: usize,
+        value: u32,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        self.add_transaction(addr, value.to_be_bytes().to_vec(), code_location)
+    }
+
+    /// Writes `values` as consecutive words in a single transaction. `addr` does not have to
+    /// be aligned to the word size.
+    fn write_slice_of_u16_le(
+        &mut self,
+        addr: usize,
+        values: &[u16],
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        let data = words_to_bytes(values, u16::to_le_bytes);
+        self.add_transaction(addr, data, code_location)
+    }
+
+    fn write_slice_of_u16_be(
+        &mut self,
+        addr: usize,
+        values: &[u16],
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        let data = words_to_bytes(values, u16::to_be_bytes);
+        self.add_transaction(addr, data, code_location)
+    }
+
+    fn write_slice_of_u32_le(
+        &mut self,
+        addr: usize,
+        values: &[u32],
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        let data = words_to_bytes(values, u32::to_le_bytes);
+        self.add_transaction(addr, data, code_location)
+    }
+
+    fn write_slice_of_u32_be(
+        &mut self,
+        addr: usize,
+        values: &[u32],
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        let data = words_to_bytes(values, u32::to_be_bytes);
+        self.add_transaction(addr, data, code_location)
+    }
+
+    fn write_slice_of_u64_le(
+        &mut self,
+        addr: usize,
+        values: &[u64],
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        let data = words_to_bytes(values, u64::to_le_bytes);
+        self.add_transaction(addr, data, code_location)
+    }
+
+    fn write_slice_of_u64_be(
+        &mut self,
+        addr: usize,
+        values: &[u64],
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        let data = words_to_bytes(values, u64::to_be_bytes);
+        self.add_transaction(addr, data, code_location)
+    }
+
+    /// Reads the bytes before the first null, looking at no more than `max_len` bytes and
+    /// stopping early at the end of the address space. The null itself is not returned.
+    fn read_bytes_until_null(&self, addr: usize, max_len: usize) -> Result<Vec<u8>, MemoryError> {
+        let size = self.address_space_size();
+        if addr >= size {
+            return Err(MemoryError::AddressOutOfRange);
+        }
+        let len = max_len.min(size - addr);
+        if len == 0 {
+            return Err(MemoryError::NullTerminatorNotFound);
+        }
+        let mut bytes = self.read(addr, len);
+        let end = bytes
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or(MemoryError::NullTerminatorNotFound)?;
+        bytes.truncate(end);
+        Ok(bytes)
+    }
+
+    /// Same as [`Memory::read_bytes_until_null`], but requires the bytes to be UTF-8.
+    fn read_string(&self, addr: usize, max_len: usize) -> Result<String, MemoryError> {
+        String::from_utf8(self.read_bytes_until_null(addr, max_len)?)
+            .map_err(|_| MemoryError::InvalidUtf8)
+    }
+
+    /// Writes `data` at `base + offset`, failing instead of wrapping if the sum overflows.
+    fn write_at_relative(
+        &mut self,
+        base: usize,
+        offset: usize,
+        data: Vec<u8>,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        let addr = base
+            .checked_add(offset)
+            .ok_or(MemoryError::AddressOverflow { base, offset })?;
+        self.add_transaction(addr, data, code_location)
+    }
+
+    fn read_relative(
+        &self,
+        base: usize,
+        offset: usize,
+        size: usize,
+    ) -> Result<Vec<u8>, MemoryError> {
+        let addr = base
+            .checked_add(offset)
+            .ok_or(MemoryError::AddressOverflow { base, offset })?;
+        match addr.checked_add(size) {
+            Some(end) if end <= self.address_space_size() => Ok(self.read(addr, size)),
+            _ => Err(MemoryError::AddressOutOfRange),
+        }
+    }
+
+    /// Writes `pattern` `count` times back to back as a single transaction. The log keeps a
+    /// single copy of the pattern.
+    fn write_repeated_pattern(
+        &mut self,
+        addr: usize,
+        pattern: &[u8],
+        count: usize,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        let len = pattern
+            .len()
+            .checked_mul(count)
+            .ok_or(MemoryError::AddressOutOfRange)?;
+        self.add_transaction_data(addr, TransactionData::repeated(pattern, len), code_location)
+    }
+
+    /// XORs `len` bytes starting at `addr` with `mask`, repeating the mask as needed, and
+    /// commits the result as one transaction.
+    fn apply_xor_mask(
+        &mut self,
+        addr: usize,
+        len: usize,
+        mask: &[u8],
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        if mask.is_empty() && len > 0 {
+            return Err(MemoryError::InvalidParams("XOR mask is empty".to_string()));
+        }
+        match addr.checked_add(len) {
+            Some(end) if end <= self.address_space_size() => {}
+            _ => return Err(MemoryError::AddressOutOfRange),
+        }
+        let mut data = if len > 0 {
+            self.read(addr, len)
+        } else {
+            Vec::new()
         };
-        self.transaction_vec_push(transaction);
-        let result = self.next_transaction();
-        debug_assert!(result.is_ok());
-        Ok(())
+        for (byte, mask_byte) in data.iter_mut().zip(mask.iter().cycle()) {
+            *byte ^= mask_byte;
+        }
+        self.add_transaction(addr, data, code_location)
+    }
+
+    fn copy_range(
+        &mut self,
+        src: usize,
+        dst: usize,
+        len: usize,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        if len == 0 {
+            return Err(MemoryError::EmptyTransaction);
+        }
+        match src.checked_add(len) {
+            Some(end) if end <= self.address_space_size() => {}
+            _ => return Err(MemoryError::AddressOutOfRange),
+        }
+        let data = self.read(src, len);
+        self.add_transaction(dst, data, code_location)
     }
 
-    fn move_to_transaction(&mut self, idx: TransactionId) -> Result<(), ()> {
+    /// Moves to the state after `idx` transactions. `idx` may equal the log length, which is the
+    /// state after the newest transaction.
+    #[must_use = "the id may be past the end of the transaction log"]
+    fn move_to_transaction(&mut self, idx: TransactionId) -> Result<(), MemoryError> {
         let id = idx.0 as usize;
-        if id >= self.transaction_vec_len() {
-            Err(())
+        if id > self.transaction_vec_len() {
+            Err(MemoryError::InvalidTransactionId(idx))
         } else if id == self.current_transaction_id() {
             Ok(())
         } else if id < self.current_transaction_id() {
@@ -98,86 +458,2780 @@ pub trait Memory: internal_memory_ops::InternalMemoryOps {
             unreachable!();
         }
     }
-}
 
-#[cfg(all(feature = "naive", test))]
-mod tests {
-    use super::*;
-    use pretty_assertions::assert_eq;
+    /// Undoes every applied transaction and returns how many were undone.
+    fn undo_all(&mut self) -> Result<usize, MemoryError> {
+        let steps = self.current_transaction_id();
+        for _ in 0..steps {
+            self.previous_transaction()?;
+        }
+        Ok(steps)
+    }
 
-    #[test]
-    fn apply_transaction() {
-        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
-        let data1 = vec![0, 1, 2, 3, 4];
-        memory.add_transaction(0x1, data1.clone(), 0x0).unwrap();
-        assert_eq!(memory.read(0x1, data1.len()), data1);
+    /// Redoes every undone transaction and returns how many were redone.
+    fn redo_all(&mut self) -> Result<usize, MemoryError> {
+        let steps = self.transaction_vec_len() - self.current_transaction_id();
+        for _ in 0..steps {
+            self.next_transaction()?;
+        }
+        Ok(steps)
+    }
 
-        let data2 = vec![4, 3, 2, 1];
-        memory.add_transaction(0x3, data2.clone(), 0x0).unwrap();
-        let result = memory.read(0x0, 8);
-        let expected_result = vec![0xab, 0, 1, 4, 3, 2, 1, 0xab];
-        assert_eq!(result, expected_result);
-        let result_tr = memory.read_transaction_ids(0x0, 8);
-        assert_eq!(result_tr.len(), 8);
-        let expected_result_tr = vec![
-            TransactionId(0),
-            TransactionId(1),
-            TransactionId(1),
-            TransactionId(2),
-            TransactionId(2),
-            TransactionId(2),
-            TransactionId(2),
-            TransactionId(0),
-        ];
-        assert_eq!(result_tr, expected_result_tr);
+    fn annotate(&mut self, id: TransactionId, key: &str, value: &str) -> Result<(), MemoryError> {
+        let entry = (id.0 as usize)
+            .checked_sub(1)
+            .and_then(|idx| self.get_mut_transaction(idx))
+            .ok_or(MemoryError::InvalidTransactionId(id))?;
+        entry.update(|transaction| {
+            transaction
+                .annotations
+                .insert(key.to_string(), value.to_string());
+        })
     }
 
-    #[test]
-    fn revert_transaction() {
-        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
-        let data1 = vec![0, 1, 2, 3, 4];
-        memory.add_transaction(0x1, data1.clone(), 0x0).unwrap();
-        assert_eq!(memory.read(0x1, data1.len()), data1);
+    fn get_annotation(&self, id: TransactionId, key: &str) -> Option<&str> {
+        let entry = self.transactions().get((id.0 as usize).checked_sub(1)?)?;
+        entry.annotation(key)
+    }
 
-        let data2 = vec![4, 3, 2, 1];
-        memory.add_transaction(0x3, data2.clone(), 0x0).unwrap();
+    fn read_at_transaction(
+        &self,
+        addr: usize,
+        size: usize,
+        id: TransactionId,
+    ) -> Result<Vec<u8>, MemoryError> {
+        let target_idx = id.0 as usize;
+        if target_idx > self.transaction_vec_len() {
+            return Err(MemoryError::InvalidTransactionId(id));
+        }
+        let current_idx = self.current_transaction_id();
+        let mut data = self.read(addr, size);
+        if target_idx < current_idx {
+            for entry in self.transactions()[target_idx..current_idx].iter().rev() {
+                let transaction = entry.load();
+                let old_data = &transaction.old_data;
+                overlay_bytes(
+                    &mut data,
+                    addr,
+                    transaction.addr,
+                    old_data.len(),
+                    |offset, dst| dst.copy_from_slice(&old_data[offset..offset + dst.len()]),
+                );
+            }
+        } else {
+            for entry in &self.transactions()[current_idx..target_idx] {
+                let transaction = entry.load();
+                let new_data = &transaction.data;
+                overlay_bytes(
+                    &mut data,
+                    addr,
+                    transaction.addr,
+                    new_data.len(),
+                    |offset, dst| new_data.copy_to(offset, dst),
+                );
+            }
+        }
+        Ok(data)
+    }
 
-        assert!(memory.previous_transaction().is_ok());
+    fn export_region(&self, addr: usize, size: usize) -> MemorySnapshot {
+        MemorySnapshot::new(
+            addr,
+            self.read(addr, size),
+            self.read_transaction_ids(addr, size),
+        )
+    }
 
-        let result = memory.read(0x0, 8);
-        let expected_result = vec![0xab, 0, 1, 2, 3, 4, 0xab, 0xab];
-        assert_eq!(result, expected_result);
-        let result_tr = memory.read_transaction_ids(0x0, 8);
-        assert_eq!(result_tr.len(), 8);
-        let expected_result_tr = vec![
-            TransactionId(0),
-            TransactionId(1),
-            TransactionId(1),
-            TransactionId(1),
-            TransactionId(1),
-            TransactionId(1),
-            TransactionId(0),
-            TransactionId(0),
-        ];
-        assert_eq!(result_tr, expected_result_tr);
+    /// Collects the bytes of the snapshotted region that differ from the snapshot.
+    fn diff_from_snapshot(&self, snapshot: &MemorySnapshot) -> MemoryDiff {
+        if snapshot.is_empty() {
+            return MemoryDiff::default();
+        }
+        let current = self.read(snapshot.base_addr(), snapshot.len());
+        MemoryDiff::between(snapshot.base_addr(), snapshot.data(), &current)
+    }
 
-        assert!(memory.previous_transaction().is_ok());
+    /// Writes every run of `diff` as its own transaction. Nothing is written unless all runs
+    /// can be applied.
+    fn apply_diff(&mut self, diff: &MemoryDiff) -> Result<(), MemoryError> {
+        for (addr, data) in diff.runs() {
+            self.validate_transaction(*addr, data)?;
+        }
+        for (addr, data) in diff.runs() {
+            self.add_transaction(*addr, data.clone(), 0x0)?;
+        }
+        Ok(())
+    }
 
-        let result = memory.read(0x0, 8);
-        let expected_result = vec![0xab, 0xab, 0xab, 0xab, 0xab, 0xab, 0xab, 0xab];
-        assert_eq!(result, expected_result);
-        let result_tr = memory.read_transaction_ids(0x0, 8);
-        assert_eq!(result_tr.len(), 8);
-        let expected_result_tr = vec![
-            TransactionId(0),
-            TransactionId(0),
-            TransactionId(0),
-            TransactionId(0),
-            TransactionId(0),
-            TransactionId(0),
-            TransactionId(0),
-            TransactionId(0),
-        ];
-        assert_eq!(result_tr, expected_result_tr);
+    fn clear_transaction_log_preserving_state(&mut self) {
+        self.transactions_mut().clear();
+        self.set_transaction_idx(0);
+        self.clear_transaction_ids();
+    }
+
+    fn truncate_transaction_log(&mut self, max_entries: usize) {
+        let len = self.transaction_vec_len();
+        if len <= max_entries {
+            return;
+        }
+        let removed = len - max_entries;
+        if self.current_transaction_id() < removed {
+            let result = self.move_to_transaction(TransactionId(removed as u32));
+            debug_assert!(result.is_ok());
+        }
+        let shift = |id: TransactionId| TransactionId(id.0.saturating_sub(removed as u32));
+        self.transactions_mut().drain(..removed);
+        for entry in self.transactions_mut() {
+            let result = entry.update(|transaction| {
+                for id in transaction.old_ids.iter_mut() {
+                    *id = shift(*id);
+                }
+            });
+            debug_assert!(result.is_ok());
+        }
+        self.remap_transaction_ids(&shift);
+        self.set_transaction_idx(self.current_transaction_id() - removed);
+    }
+
+    fn truncate_future(&mut self, current: TransactionId) -> Result<(), MemoryError> {
+        let current_idx = current.0 as usize;
+        if current_idx > self.transaction_vec_len() {
+            return Err(MemoryError::InvalidTransactionId(current));
+        }
+        if self.current_transaction_id() > current_idx {
+            self.move_to_transaction(current)?;
+        }
+        let transactions = self.transactions_mut();
+        transactions.truncate(current_idx);
+        transactions.shrink_to_fit();
+        Ok(())
+    }
+
+    /// Undoes the transaction that produced state `id` while keeping the effects of every
+    /// later transaction. The undo is recorded as a new transaction that restores the
+    /// overwritten bytes, so it fails if a later applied transaction wrote any of them.
+    fn apply_transaction_inverse(&mut self, id: TransactionId) -> Result<(), MemoryError> {
+        let current_idx = self.current_transaction_id();
+        let target_idx = match (id.0 as usize).checked_sub(1) {
+            Some(idx) if idx < current_idx => idx,
+            _ => return Err(MemoryError::InvalidTransactionId(id)),
+        };
+        let transactions = self.transactions();
+        let target = transactions[target_idx].load();
+        if let Some(offset) = transactions[target_idx + 1..current_idx]
+            .iter()
+            .position(|later| target.overlaps_range(later.address(), later.byte_count()))
+        {
+            return Err(MemoryError::ConflictingTransaction(TransactionId(
+                (target_idx + offset + 2) as u32,
+            )));
+        }
+        let (addr, old_data, code_location) =
+            (target.addr, target.old_data.to_vec(), target.code_location);
+        self.add_transaction(addr, old_data, code_location)
+    }
+
+    /// Drops the redo stack and returns the number of discarded transactions.
+    fn clear_future_transactions(&mut self) -> usize {
+        let current_idx = self.current_transaction_id();
+        let transactions = self.transactions_mut();
+        let removed = transactions.len() - current_idx;
+        transactions.truncate(current_idx);
+        removed
+    }
+
+    fn reconstruct_memory_at_transaction(&self, id: TransactionId) -> Result<Self, MemoryError>
+    where
+        Self: Sized + Clone,
+    {
+        let mut memory = self.clone();
+        memory.move_to_transaction(id)?;
+        memory.clear_transaction_log_preserving_state();
+        Ok(memory)
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(TransactionId(self.current_transaction_id() as u32))
+    }
+
+    fn restore(&mut self, checkpoint: Checkpoint) -> Result<(), MemoryError> {
+        self.move_to_transaction(checkpoint.0)
+    }
+
+    fn write_data_if_changed_since(
+        &mut self,
+        addr: usize,
+        data: Vec<u8>,
+        since: TransactionId,
+        code_location: usize,
+    ) -> Result<bool, MemoryError> {
+        self.validate_transaction(addr, &data)?;
+        let written_after = self
+            .read_transaction_ids(addr, data.len())
+            .into_iter()
+            .any(|id| id > since);
+        if written_after {
+            return Ok(false);
+        }
+        self.add_transaction(addr, data, code_location)?;
+        Ok(true)
+    }
+
+    fn read_range_changed_since(
+        &self,
+        addr: usize,
+        size: usize,
+        since: TransactionId,
+    ) -> Vec<bool> {
+        self.read_transaction_ids(addr, size)
+            .into_iter()
+            .map(|id| id > since)
+            .collect()
+    }
+
+    /// Like [`Memory::read_range_changed_since`], but bytes whose `mask` entry is `false` are
+    /// reported as unchanged without being checked.
+    fn read_range_changed_since_masked(
+        &self,
+        addr: usize,
+        size: usize,
+        since: TransactionId,
+        mask: &[bool],
+    ) -> Vec<bool> {
+        assert_eq!(mask.len(), size);
+        self.read_transaction_ids(addr, size)
+            .into_iter()
+            .zip(mask)
+            .map(|(id, &checked)| checked && id > since)
+            .collect()
+    }
+
+    fn compare_and_swap(
+        &mut self,
+        addr: usize,
+        expected: &[u8],
+        new_data: Vec<u8>,
+        code_location: usize,
+    ) -> Result<bool, MemoryError> {
+        if expected.len() != new_data.len() {
+            return Err(MemoryError::SizeMismatch {
+                expected: expected.len(),
+                actual: new_data.len(),
+            });
+        }
+        self.validate_transaction(addr, &new_data)?;
+        if self.read(addr, expected.len()) != expected {
+            return Ok(false);
+        }
+        self.add_transaction(addr, new_data, code_location)?;
+        Ok(true)
+    }
+
+    /// Changes the value returned for never-written addresses.
+    ///
+    /// Bytes never touched by any transaction take the new default, and so do the matching
+    /// `old_data` bytes, so undo restores the rebased value. Initialized bytes, such as a
+    /// loaded image, keep their value even where it equals the old default.
+    fn rebase(&mut self, new_default: u8) {
+        let old_default = self.default_value();
+        if old_default == new_default {
+            return;
+        }
+        self.replace_default_value(new_default);
+        let mut transactions = std::mem::take(self.transactions_mut());
+        for entry in transactions.iter_mut() {
+            let transaction = entry.load();
+            let needs_rebase = |offset: usize| {
+                transaction.old_ids[offset] == TransactionId(0)
+                    && transaction.old_data[offset] == old_default
+                    && !self.is_initialized(transaction.addr + offset)
+            };
+            if !(0..transaction.old_data.len()).any(needs_rebase) {
+                continue;
+            }
+            let old_data: Vec<u8> = (0..transaction.old_data.len())
+                .map(|offset| {
+                    if needs_rebase(offset) {
+                        new_default
+                    } else {
+                        transaction.old_data[offset]
+                    }
+                })
+                .collect();
+            let result = entry.update(|transaction| transaction.old_data = old_data.into());
+            debug_assert!(result.is_ok());
+        }
+        *self.transactions_mut() = transactions;
+    }
+
+    fn assert_transaction_id_consistency(&self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        if let Err(errors) = self.validate_integrity() {
+            let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+            panic!("{} integrity errors:\n{}", errors.len(), errors.join("\n"));
+        }
+    }
+
+    /// Checks every internal invariant and reports each violation found.
+    #[cold]
+    fn validate_integrity(&self) -> Result<(), Vec<IntegrityError>> {
+        let mut errors = Vec::new();
+        let current = self.current_transaction_id();
+        let len = self.transaction_vec_len();
+        if current > len {
+            errors.push(IntegrityError::TransactionIdxOutOfRange { current, len });
+        }
+        for (chunk_addr, ids) in transaction_id_chunks(self, 0, self.address_space_size()) {
+            for (offset, id) in ids.into_iter().enumerate() {
+                if id.0 as usize > current {
+                    errors.push(IntegrityError::FutureTransactionId {
+                        addr: chunk_addr + offset,
+                        id,
+                    });
+                }
+            }
+        }
+        for (index, entry) in self.transactions().iter().enumerate() {
+            let transaction = entry.load();
+            let data = transaction.data.len();
+            let old_data = transaction.old_data.len();
+            let old_ids = transaction.old_ids.len();
+            if old_data != data || old_ids != data {
+                errors.push(IntegrityError::LengthMismatch {
+                    index,
+                    data,
+                    old_data,
+                    old_ids,
+                });
+            }
+            for (offset, id) in transaction.old_ids.iter().enumerate() {
+                if id.0 as usize > index {
+                    errors.push(IntegrityError::DanglingOldId {
+                        index,
+                        addr: transaction.addr + offset,
+                        id: *id,
+                    });
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn memory_eq<M: Memory>(&self, other: &M) -> bool {
+        let size = self.address_space_size();
+        if size != other.address_space_size() {
+            return false;
+        }
+        address_chunks(0, size).all(|(addr, chunk_size)| {
+            self.read(addr, chunk_size) == other.read(addr, chunk_size)
+                && self.read_transaction_ids(addr, chunk_size)
+                    == other.read_transaction_ids(addr, chunk_size)
+        })
+    }
+
+    /// Contiguous `(start_addr, end_addr_inclusive, id)` regions last written by the same
+    /// transaction, in ascending address order.
+    fn memory_map(&self) -> Vec<(usize, usize, TransactionId)> {
+        let mut map: Vec<(usize, usize, TransactionId)> = Vec::new();
+        for (chunk_addr, ids) in transaction_id_chunks(self, 0, self.address_space_size()) {
+            for (addr, id) in (chunk_addr..).zip(ids) {
+                match map.last_mut() {
+                    Some((_, end, last_id)) if *last_id == id => *end = addr,
+                    _ => map.push((addr, addr, id)),
+                }
+            }
+        }
+        map
+    }
+
+    fn transaction_log_is_sorted_by_addr(&self) -> bool {
+        self.transactions()
+            .windows(2)
+            .all(|pair| pair[0].address() <= pair[1].address())
+    }
+
+    fn transaction_log_is_chronological(&self) -> bool {
+        self.transactions().windows(2).all(|pair| {
+            let (first, second) = (&pair[0], &pair[1]);
+            first.address() + first.byte_count() <= second.address()
+                || second.address() + second.byte_count() <= first.address()
+        })
+    }
+
+    fn find_address_of_pattern_relative_to(
+        &self,
+        base: usize,
+        pattern: &[Option<u8>],
+    ) -> Option<usize> {
+        let size = self.address_space_size();
+        if pattern.is_empty() || pattern.len() > size {
+            return None;
+        }
+        let last_start = size - pattern.len();
+        let base = base.min(last_start);
+        let matches_at = |data: &[u8], offset: usize| {
+            data[offset..offset + pattern.len()]
+                .iter()
+                .zip(pattern)
+                .all(|(byte, expected)| expected.is_none_or(|expected| expected == *byte))
+        };
+        // Candidate starts are read one window at a time on each side of `base` and probed in
+        // order of increasing distance, forward first.
+        let read_window = |first: usize, count: usize| {
+            (first, count, self.read(first, count + pattern.len() - 1))
+        };
+        let mut distance = 0;
+        loop {
+            let forward = (base + distance <= last_start).then(|| {
+                let first = base + distance;
+                read_window(first, CHUNK_SIZE.min(last_start - first + 1))
+            });
+            let backward = base.checked_sub(distance).map(|last| {
+                let count = CHUNK_SIZE.min(last + 1);
+                read_window(last + 1 - count, count)
+            });
+            if forward.is_none() && backward.is_none() {
+                return None;
+            }
+            for step in 0..CHUNK_SIZE {
+                if let Some((first, count, data)) = &forward {
+                    if step < *count && matches_at(data, step) {
+                        return Some(first + step);
+                    }
+                }
+                if let Some((first, count, data)) = &backward {
+                    let offset = count.wrapping_sub(step + 1);
+                    if step < *count && distance + step > 0 && matches_at(data, offset) {
+                        return Some(first + offset);
+                    }
+                }
+            }
+            distance += CHUNK_SIZE;
+        }
+    }
+
+    fn optimize_transaction_storage(&mut self) -> usize {
+        let transactions = self.transactions_mut();
+        let mut shared = 0;
+        for idx in 1..transactions.len() {
+            let (previous, next) = transactions.split_at_mut(idx);
+            // Entries that do not keep a `Transaction` on the heap have nothing to share.
+            let (Cow::Borrowed(previous), Cow::Borrowed(next_transaction)) =
+                (previous[idx - 1].load(), next[0].load())
+            else {
+                continue;
+            };
+            let Some(data) = previous.data.as_slice() else {
+                continue;
+            };
+            if Arc::ptr_eq(data, &next_transaction.old_data) || *data != next_transaction.old_data {
+                continue;
+            }
+            let data = Arc::clone(data);
+            let result = next[0].update(|transaction| transaction.old_data = data);
+            debug_assert!(result.is_ok());
+            shared += 1;
+        }
+        shared
+    }
+
+    /// The transaction that produced the state `id`; `None` for the initial state.
+    fn transaction_at(&self, id: TransactionId) -> Option<Cow<'_, Transaction>> {
+        (id.0 as usize)
+            .checked_sub(1)
+            .and_then(|idx| self.transactions().get(idx))
+            .map(TransactionStorage::load)
+    }
+
+    /// Whether transaction `id` wrote any byte of `addr..addr + size`. `false` for the
+    /// initial state and unknown ids.
+    fn transaction_touches(&self, id: TransactionId, addr: usize, size: usize) -> bool {
+        self.transaction_at(id)
+            .is_some_and(|transaction| transaction.overlaps_range(addr, size))
+    }
+
+    /// [`Memory::transaction_touches`] for every `(addr, size)` pair of `ranges`, looking the
+    /// transaction up once.
+    fn transaction_touches_ranges(
+        &self,
+        id: TransactionId,
+        ranges: &[(usize, usize)],
+    ) -> Vec<bool> {
+        let Some(transaction) = self.transaction_at(id) else {
+            return vec![false; ranges.len()];
+        };
+        ranges
+            .iter()
+            .map(|&(addr, size)| transaction.overlaps_range(addr, size))
+            .collect()
+    }
+
+    fn current_transaction(&self) -> Option<Cow<'_, Transaction>> {
+        self.current_transaction_id()
+            .checked_sub(1)
+            .map(|idx| self.transactions()[idx].load())
+    }
+
+    /// Address of the write that produced the current state.
+    fn last_write_addr(&self) -> Option<usize> {
+        self.current_transaction_id()
+            .checked_sub(1)
+            .map(|idx| self.transactions()[idx].address())
+    }
+
+    /// Size of the write that produced the current state.
+    fn last_write_size(&self) -> Option<usize> {
+        self.current_transaction_id()
+            .checked_sub(1)
+            .map(|idx| self.transactions()[idx].byte_count())
+    }
+
+    /// The `n` most recently applied transactions, newest first.
+    fn last_n_transactions(&self, n: usize) -> impl Iterator<Item = Cow<'_, Transaction>> {
+        self.transactions()[..self.current_transaction_id()]
+            .iter()
+            .rev()
+            .take(n)
+            .map(TransactionStorage::load)
+    }
+
+    fn clone_transaction_log(&self) -> Vec<Transaction> {
+        self.transactions()
+            .iter()
+            .map(|entry| entry.load().into_owned())
+            .collect()
+    }
+
+    fn replay(&mut self, log: &TransactionLog) -> Result<(), MemoryError> {
+        self.replay_partial(log, log.transactions().len())
+    }
+
+    /// Applies the first `n` transactions of `log` on top of the current state.
+    fn replay_partial(&mut self, log: &TransactionLog, n: usize) -> Result<(), MemoryError> {
+        for (index, transaction) in log.transactions().iter().take(n).enumerate() {
+            self.add_transaction_data(
+                transaction.addr,
+                transaction.data.clone(),
+                transaction.code_location,
+            )
+            .map_err(|error| MemoryError::ReplayFailed {
+                index,
+                source: Box::new(error),
+            })?;
+            if transaction.annotations.is_empty() {
+                continue;
+            }
+            let last_idx = self.transaction_vec_len() - 1;
+            self.get_mut_transaction(last_idx)
+                .unwrap()
+                .update(|last| last.annotations = transaction.annotations.clone())
+                .map_err(|error| MemoryError::ReplayFailed {
+                    index,
+                    source: Box::new(error),
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Merges runs of consecutive transactions that cover a contiguous address range and
+    /// returns the number of merges. Transactions on opposite sides of the current position
+    /// are never merged.
+    fn compact_adjacent_transactions(&mut self) -> usize {
+        let cursor = self.current_transaction_id();
+        let transactions = std::mem::take(self.transactions_mut());
+        let len = transactions.len();
+        let new_index_of = compacted_indices(&transactions, cursor);
+        let mut compacted = Vec::with_capacity(len);
+        let mut run: Option<Transaction> = None;
+        for (idx, entry) in transactions.into_iter().enumerate() {
+            let ends_run = new_index_of.get(idx + 1) != Some(&new_index_of[idx]);
+            if run.is_none() && ends_run {
+                compacted.push(entry);
+                continue;
+            }
+            let transaction = entry.load().into_owned();
+            let merged = match run.take() {
+                Some(last) => Transaction::merge(last, transaction).unwrap(),
+                None => transaction,
+            };
+            if ends_run {
+                // `compacted_indices` keeps every run within `MAX_BYTES`.
+                compacted.push(Self::TransactionStorage::store(merged).unwrap());
+            } else {
+                run = Some(merged);
+            }
+        }
+        let map = |id: TransactionId| match id.0 {
+            0 => id,
+            id => TransactionId(new_index_of[id as usize - 1] as u32 + 1),
+        };
+        for entry in compacted.iter_mut() {
+            let result = entry.update(|transaction| {
+                for id in transaction.old_ids.iter_mut() {
+                    *id = map(*id);
+                }
+            });
+            debug_assert!(result.is_ok());
+        }
+        self.remap_transaction_ids(&map);
+        let merged = len - compacted.len();
+        *self.transactions_mut() = compacted;
+        self.set_transaction_idx(map(TransactionId(cursor as u32)).0 as usize);
+        merged
+    }
+
+    fn transaction_size_distribution(&self) -> HashMap<usize, usize> {
+        let mut distribution = HashMap::new();
+        for entry in self.transactions() {
+            *distribution.entry(entry.byte_count()).or_insert(0) += 1;
+        }
+        distribution
+    }
+
+    /// Estimates the heap memory held by the transaction log, in bytes. Annotations are not
+    /// counted, and fills count only the bytes actually stored.
+    fn transaction_history_size_bytes(&self) -> usize {
+        let payload: usize = self
+            .transactions()
+            .iter()
+            .map(TransactionStorage::heap_size)
+            .sum();
+        payload + self.transaction_vec_capacity() * std::mem::size_of::<Self::TransactionStorage>()
+    }
+
+    /// Newest transaction that wrote a byte of the region, [`TransactionId::INITIAL`] if none
+    /// did.
+    fn max_transaction_id_in_region(&self, addr: usize, size: usize) -> TransactionId {
+        transaction_id_chunks(self, addr, size)
+            .filter_map(|(_, ids)| ids.into_iter().max())
+            .max()
+            .unwrap_or(TransactionId::INITIAL)
+    }
+
+    /// Oldest transaction id found in the region; [`TransactionId::INITIAL`] if any byte was
+    /// never written.
+    fn min_transaction_id_in_region(&self, addr: usize, size: usize) -> TransactionId {
+        transaction_id_chunks(self, addr, size)
+            .filter_map(|(_, ids)| ids.into_iter().min())
+            .min()
+            .unwrap_or(TransactionId::INITIAL)
+    }
+
+    /// Passes the bytes of `addr..addr + size` to `visitor` in one or more consecutive
+    /// slices, without collecting them into a single buffer.
+    fn visit_data(&self, addr: usize, size: usize, visitor: &mut dyn FnMut(&[u8])) {
+        for (chunk_addr, chunk_size) in address_chunks(addr, size) {
+            visitor(&self.read(chunk_addr, chunk_size));
+        }
+    }
+
+    /// Number of occurrences of every byte value in `addr..addr + size`.
+    fn byte_frequency_histogram(&self, addr: usize, size: usize) -> [u32; 256] {
+        let mut histogram = [0; 256];
+        self.visit_data(addr, size, &mut |bytes| {
+            for &byte in bytes {
+                histogram[byte as usize] += 1;
+            }
+        });
+        histogram
+    }
+
+    /// CRC-32 (IEEE 802.3) of `addr..addr + size`.
+    fn memory_checksum(&self, addr: usize, size: usize) -> u32 {
+        let mut crc = checksum::Crc32::new();
+        self.visit_data(addr, size, &mut |bytes| crc.update(bytes));
+        crc.finish()
+    }
+
+    /// CRC-32 of `addr..addr + size` as it was in the state `id`.
+    fn checksum_at_transaction(
+        &self,
+        addr: usize,
+        size: usize,
+        id: TransactionId,
+    ) -> Result<u32, MemoryError> {
+        let mut crc = checksum::Crc32::new();
+        if size > 0 {
+            crc.update(&self.read_at_transaction(addr, size, id)?);
+        }
+        Ok(crc.finish())
+    }
+
+    /// Sums [`Transaction::byte_count`] over the whole transaction log. Undone transactions
+    /// still on the redo stack are counted, so undoing a write doesn't change the total.
+    fn total_bytes_written(&self) -> usize {
+        self.transactions()
+            .iter()
+            .map(TransactionStorage::byte_count)
+            .sum()
+    }
+
+    /// Number of distinct addresses written by any transaction in the log, including the redo
+    /// stack.
+    fn unique_bytes_written(&self) -> usize {
+        let mut written = vec![0u64; self.address_space_size().div_ceil(64)];
+        for entry in self.transactions() {
+            for addr in entry.address()..entry.address() + entry.byte_count() {
+                written[addr / 64] |= 1 << (addr % 64);
+            }
+        }
+        written.iter().map(|word| word.count_ones() as usize).sum()
+    }
+}
+
+/// Largest piece read at once by the default methods that walk a whole region.
+const CHUNK_SIZE: usize = 4096;
+
+/// Splits `addr..addr + size` into `(chunk_addr, chunk_size)` pieces of at most
+/// [`CHUNK_SIZE`] bytes.
+fn address_chunks(addr: usize, size: usize) -> impl Iterator<Item = (usize, usize)> {
+    let end = addr
+        .checked_add(size)
+        .expect("address range overflows usize");
+    (addr..end)
+        .step_by(CHUNK_SIZE)
+        .map(move |chunk_addr| (chunk_addr, CHUNK_SIZE.min(end - chunk_addr)))
+}
+
+/// Transaction ids of `addr..addr + size`, read one chunk at a time and paired with the
+/// address of the chunk.
+fn transaction_id_chunks<M: Memory + ?Sized>(
+    memory: &M,
+    addr: usize,
+    size: usize,
+) -> impl Iterator<Item = (usize, Vec<TransactionId>)> + '_ {
+    address_chunks(addr, size).map(move |(chunk_addr, chunk_size)| {
+        (
+            chunk_addr,
+            memory.read_transaction_ids(chunk_addr, chunk_size),
+        )
+    })
+}
+
+fn words_to_bytes<T: Copy, const N: usize>(values: &[T], to_bytes: fn(T) -> [u8; N]) -> Vec<u8> {
+    values.iter().flat_map(|&value| to_bytes(value)).collect()
+}
+
+/// Position of each transaction in the log after `compact_adjacent_transactions`, with the
+/// current position at `cursor`. Runs of transactions that cover one contiguous range of at
+/// most `S::MAX_BYTES` end up at the same position.
+fn compacted_indices<S: TransactionStorage>(transactions: &[S], cursor: usize) -> Vec<usize> {
+    let mut new_index_of = Vec::with_capacity(transactions.len());
+    let mut run: Option<(usize, usize)> = None;
+    let mut runs = 0;
+    for (idx, entry) in transactions.iter().enumerate() {
+        let (start, end) = (entry.address(), entry.address() + entry.byte_count());
+        run = match run {
+            Some((run_start, run_end))
+                if idx != cursor
+                    && run_start <= end
+                    && start <= run_end
+                    && run_end.max(end) - run_start.min(start) <= S::MAX_BYTES =>
+            {
+                Some((run_start.min(start), run_end.max(end)))
+            }
+            _ => {
+                runs += 1;
+                Some((start, end))
+            }
+        };
+        new_index_of.push(runs - 1);
+    }
+    new_index_of
+}
+
+/// Overwrites the part of `data` (at `addr`) that overlaps `src_len` bytes at `src_addr`.
+/// `copy` fills its slice with the source bytes starting at the given source offset.
+fn overlay_bytes(
+    data: &mut [u8],
+    addr: usize,
+    src_addr: usize,
+    src_len: usize,
+    copy: impl FnOnce(usize, &mut [u8]),
+) {
+    let start = addr.max(src_addr);
+    let end = (addr + data.len()).min(src_addr + src_len);
+    if start < end {
+        copy(start - src_addr, &mut data[start - addr..end - addr]);
+    }
+}
+
+/// Checks every precondition of a write of `len` bytes at `addr`.
+fn validate_write<M: Memory + ?Sized>(
+    memory: &M,
+    addr: usize,
+    len: usize,
+) -> Result<(), MemoryError> {
+    if len == 0 {
+        return Err(MemoryError::EmptyTransaction);
+    }
+    if len > M::TransactionStorage::MAX_BYTES {
+        return Err(MemoryError::TransactionTooLarge {
+            size: len,
+            max_size: M::TransactionStorage::MAX_BYTES,
+        });
+    }
+    if memory.transaction_vec_len() != memory.current_transaction_id() {
+        return Err(MemoryError::NotAtLatestTransaction);
+    }
+    match addr.checked_add(len) {
+        Some(end) if end <= memory.address_space_size() => {}
+        _ => return Err(MemoryError::AddressOutOfRange),
+    }
+    #[cfg(feature = "protection")]
+    if let Some(addr) = memory.protection().first_readonly(addr..addr + len) {
+        return Err(MemoryError::WriteProtected(addr));
+    }
+    Ok(())
+}
+
+/// Prints the `verbose_debug` line for a write of `len` bytes at `addr`.
+#[cfg(feature = "verbose_debug")]
+fn trace_write(addr: usize, len: usize, code_location: usize) {
+    eprintln!("WRITE addr={addr:#x} len={len} ip={code_location:#x}");
+}
+
+/// Records `data` at `addr` as a new transaction and applies it. Concrete memories implement
+/// `add_transaction_data` with this.
+fn record_transaction<M: Memory + ?Sized>(
+    memory: &mut M,
+    addr: usize,
+    data: TransactionData,
+    code_location: usize,
+) -> Result<(), MemoryError> {
+    validate_write(memory, addr, data.len())?;
+    #[cfg(feature = "verbose_debug")]
+    trace_write(addr, data.len(), code_location);
+    let old_data = memory.read(addr, data.len()).into();
+    let old_ids = memory.read_transaction_ids(addr, data.len());
+    let transaction = Transaction {
+        addr,
+        data,
+        old_ids,
+        old_data,
+        code_location,
+        annotations: HashMap::new(),
+    };
+    memory.transaction_vec_push(M::TransactionStorage::store(transaction)?);
+    let result = memory.next_transaction();
+    debug_assert!(result.is_ok());
+    Ok(())
+}
+
+#[cfg(all(feature = "naive", test))]
+mod tests {
+    use super::*;
+    use internal_memory_ops::InternalMemoryOps;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn undo_all_redo_all() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert_eq!(memory.undo_all(), Ok(0));
+        assert_eq!(memory.redo_all(), Ok(0));
+
+        memory.add_transaction(0x0, vec![1, 2], 0x0).unwrap();
+        memory.add_transaction(0x1, vec![3], 0x0).unwrap();
+        memory.add_transaction(0x4, vec![4], 0x0).unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.undo_all(), Ok(2));
+        assert_eq!(memory.current_transaction_id(), 0);
+        assert_eq!(memory.read(0x0, 5), vec![0xab; 5]);
+        assert_eq!(memory.undo_all(), Ok(0));
+
+        assert_eq!(memory.redo_all(), Ok(3));
+        assert_eq!(memory.current_transaction_id(), 3);
+        assert_eq!(memory.read(0x0, 5), vec![1, 3, 0xab, 0xab, 4]);
+        assert_eq!(memory.redo_all(), Ok(0));
+    }
+
+    #[test]
+    fn write_u32() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.write_u32_le(0x1, 0x1234_5678, 0x0).unwrap();
+        memory.write_u32_be(0x8, 0x1234_5678, 0x0).unwrap();
+        assert_eq!(
+            memory.read(0x0, 12),
+            vec![0xab, 0x78, 0x56, 0x34, 0x12, 0xab, 0xab, 0xab, 0x12, 0x34, 0x56, 0x78]
+        );
+        assert_eq!(memory.transaction_vec_len(), 2);
+        assert_eq!(
+            memory.write_u32_be(0xd, 0x0, 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+    }
+
+    #[test]
+    fn verbose_debug_compiles_away() {
+        // Spelled backwards so the test binary does not contain the needle itself.
+        let needle: Vec<u8> = b"=rdda ETIRW".iter().rev().copied().collect();
+        let binary = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x0, vec![1], 0x0).unwrap();
+
+        let found = binary.windows(needle.len()).any(|window| window == needle);
+        assert_eq!(found, cfg!(feature = "verbose_debug"));
+    }
+
+    #[test]
+    fn apply_transaction() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        let data1 = vec![0, 1, 2, 3, 4];
+        memory.add_transaction(0x1, data1.clone(), 0x0).unwrap();
+        assert_eq!(memory.read(0x1, data1.len()), data1);
+
+        let data2 = vec![4, 3, 2, 1];
+        memory.add_transaction(0x3, data2.clone(), 0x0).unwrap();
+        let result = memory.read(0x0, 8);
+        let expected_result = vec![0xab, 0, 1, 4, 3, 2, 1, 0xab];
+        assert_eq!(result, expected_result);
+        let result_tr = memory.read_transaction_ids(0x0, 8);
+        assert_eq!(result_tr.len(), 8);
+        let expected_result_tr = vec![
+            TransactionId(0),
+            TransactionId(1),
+            TransactionId(1),
+            TransactionId(2),
+            TransactionId(2),
+            TransactionId(2),
+            TransactionId(2),
+            TransactionId(0),
+        ];
+        assert_eq!(result_tr, expected_result_tr);
+    }
+
+    #[test]
+    fn revert_transaction() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        let data1 = vec![0, 1, 2, 3, 4];
+        memory.add_transaction(0x1, data1.clone(), 0x0).unwrap();
+        assert_eq!(memory.read(0x1, data1.len()), data1);
+
+        let data2 = vec![4, 3, 2, 1];
+        memory.add_transaction(0x3, data2.clone(), 0x0).unwrap();
+
+        assert!(memory.previous_transaction().is_ok());
+
+        let result = memory.read(0x0, 8);
+        let expected_result = vec![0xab, 0, 1, 2, 3, 4, 0xab, 0xab];
+        assert_eq!(result, expected_result);
+        let result_tr = memory.read_transaction_ids(0x0, 8);
+        assert_eq!(result_tr.len(), 8);
+        let expected_result_tr = vec![
+            TransactionId(0),
+            TransactionId(1),
+            TransactionId(1),
+            TransactionId(1),
+            TransactionId(1),
+            TransactionId(1),
+            TransactionId(0),
+            TransactionId(0),
+        ];
+        assert_eq!(result_tr, expected_result_tr);
+
+        assert!(memory.previous_transaction().is_ok());
+
+        let result = memory.read(0x0, 8);
+        let expected_result = vec![0xab, 0xab, 0xab, 0xab, 0xab, 0xab, 0xab, 0xab];
+        assert_eq!(result, expected_result);
+        let result_tr = memory.read_transaction_ids(0x0, 8);
+        assert_eq!(result_tr.len(), 8);
+        let expected_result_tr = vec![
+            TransactionId(0),
+            TransactionId(0),
+            TransactionId(0),
+            TransactionId(0),
+            TransactionId(0),
+            TransactionId(0),
+            TransactionId(0),
+            TransactionId(0),
+        ];
+        assert_eq!(result_tr, expected_result_tr);
+    }
+
+    #[test]
+    fn write_data_if_changed_since() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x2, vec![0, 1, 2], 0x0).unwrap();
+        let since = TransactionId(memory.current_transaction_id() as u32);
+
+        assert_eq!(
+            memory.write_data_if_changed_since(0x0, vec![5, 6], since, 0x0),
+            Ok(true)
+        );
+        assert_eq!(memory.read(0x0, 5), vec![5, 6, 0, 1, 2]);
+
+        assert_eq!(
+            memory.write_data_if_changed_since(0x1, vec![7, 7], since, 0x0),
+            Ok(false)
+        );
+        assert_eq!(memory.read(0x0, 5), vec![5, 6, 0, 1, 2]);
+        assert_eq!(memory.current_transaction_id(), 2);
+
+        assert_eq!(
+            memory.write_data_if_changed_since(0x5, vec![7, 7], since, 0x0),
+            Ok(true)
+        );
+        assert_eq!(memory.read(0x4, 4), vec![2, 7, 7, 0xab]);
+
+        assert_eq!(
+            memory.write_data_if_changed_since(usize::MAX, vec![7, 7], since, 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            memory.write_data_if_changed_since(0x0, vec![], since, 0x0),
+            Err(MemoryError::EmptyTransaction)
+        );
+    }
+
+    #[test]
+    fn memory_eq() {
+        let mut naive_memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        assert!(naive_memory.memory_eq(&paged_memory));
+
+        naive_memory
+            .add_transaction(0x3, vec![0, 1, 2], 0x0)
+            .unwrap();
+        assert!(!naive_memory.memory_eq(&paged_memory));
+        paged_memory
+            .add_transaction(0x3, vec![0, 1, 2], 0x0)
+            .unwrap();
+        assert!(naive_memory.memory_eq(&paged_memory));
+        assert!(paged_memory.memory_eq(&naive_memory));
+
+        naive_memory.previous_transaction().unwrap();
+        assert!(!naive_memory.memory_eq(&paged_memory));
+
+        let other_default = PagedMemory::<4, 4>::new(0x00);
+        assert!(!PagedMemory::<4, 4>::new(0xab).memory_eq(&other_default));
+        assert!(!PagedMemory::<4, 4>::new(0xab).memory_eq(&PagedMemory::<8, 4>::new(0xab)));
+    }
+
+    #[test]
+    fn transaction_log_is_sorted_by_addr() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert!(memory.transaction_log_is_sorted_by_addr());
+        memory.add_transaction(0x1, vec![0, 1], 0x0).unwrap();
+        memory.add_transaction(0x1, vec![2], 0x0).unwrap();
+        memory.add_transaction(0x4, vec![3, 4], 0x0).unwrap();
+        assert!(memory.transaction_log_is_sorted_by_addr());
+        memory.add_transaction(0x2, vec![5], 0x0).unwrap();
+        assert!(!memory.transaction_log_is_sorted_by_addr());
+    }
+
+    #[test]
+    fn transaction_log_is_chronological() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert!(memory.transaction_log_is_chronological());
+        memory.add_transaction(0x1, vec![0, 1], 0x0).unwrap();
+        memory.add_transaction(0x3, vec![2], 0x0).unwrap();
+        memory.add_transaction(0x0, vec![3], 0x0).unwrap();
+        assert!(memory.transaction_log_is_chronological());
+        memory.add_transaction(0x0, vec![4, 5], 0x0).unwrap();
+        assert!(!memory.transaction_log_is_chronological());
+    }
+
+    #[test]
+    fn move_to_transaction() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x1, vec![0, 1], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![2, 3], 0x0).unwrap();
+
+        memory.move_to_transaction(TransactionId(0)).unwrap();
+        assert_eq!(memory.read(0x0, 4), vec![0xab; 4]);
+        memory.move_to_transaction(TransactionId(2)).unwrap();
+        assert_eq!(memory.current_transaction_id(), 2);
+        assert_eq!(memory.read(0x0, 4), vec![0xab, 0, 2, 3]);
+        assert_eq!(
+            memory.move_to_transaction(TransactionId(3)),
+            Err(MemoryError::InvalidTransactionId(TransactionId(3)))
+        );
+        assert_eq!(memory.current_transaction_id(), 2);
+    }
+
+    #[test]
+    fn checkpoint_restore() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x1, vec![0, 1], 0x0).unwrap();
+        let checkpoint = memory.checkpoint();
+        memory.add_transaction(0x2, vec![2, 3], 0x0).unwrap();
+        memory.add_transaction(0x3, vec![4, 5], 0x0).unwrap();
+        let head = memory.checkpoint();
+        let head_state = memory.clone();
+
+        memory.restore(checkpoint).unwrap();
+        assert_eq!(memory.current_transaction_id(), 1);
+        assert_eq!(memory.read(0x0, 5), vec![0xab, 0, 1, 0xab, 0xab]);
+
+        memory.restore(head).unwrap();
+        assert_eq!(memory, head_state);
+    }
+
+    #[test]
+    fn read_at_transaction() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x1, vec![0, 1, 2], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![3, 4, 5], 0x0).unwrap();
+        memory.add_transaction(0x0, vec![6, 7], 0x0).unwrap();
+        memory.move_to_transaction(TransactionId(2)).unwrap();
+        let before = memory.clone();
+
+        let expected = [
+            vec![0xab, 0xab, 0xab, 0xab, 0xab, 0xab],
+            vec![0xab, 0, 1, 2, 0xab, 0xab],
+            vec![0xab, 0, 3, 4, 5, 0xab],
+            vec![6, 7, 3, 4, 5, 0xab],
+        ];
+        for (id, expected) in expected.iter().enumerate() {
+            let data = memory
+                .read_at_transaction(0x0, 6, TransactionId(id as u32))
+                .unwrap();
+            assert_eq!(&data, expected);
+        }
+        assert_eq!(
+            memory.read_at_transaction(0x0, 6, TransactionId(4)),
+            Err(MemoryError::InvalidTransactionId(TransactionId(4)))
+        );
+        assert_eq!(memory, before);
+    }
+
+    #[test]
+    fn copy_range() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x1, vec![1, 2, 3], 0x0).unwrap();
+        memory.copy_range(0x1, 0xd, 3, 0x0).unwrap();
+        assert_eq!(memory.read(0xc, 4), vec![0xab, 1, 2, 3]);
+        assert_eq!(
+            memory.copy_range(0x1, 0x4, 0, 0x0),
+            Err(MemoryError::EmptyTransaction)
+        );
+        assert_eq!(
+            memory.copy_range(usize::MAX, 0x4, 2, 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            memory.copy_range(0xf, 0x4, 2, 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(memory.current_transaction_id(), 2);
+    }
+
+    #[test]
+    fn transaction_size_distribution() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert!(memory.transaction_size_distribution().is_empty());
+        memory.add_transaction(0x0, vec![0; 4], 0x0).unwrap();
+        memory.add_transaction(0x4, vec![1], 0x0).unwrap();
+        memory.add_transaction(0x8, vec![2; 4], 0x0).unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(
+            memory.transaction_size_distribution(),
+            HashMap::from([(4, 2), (1, 1)])
+        );
+    }
+
+    #[test]
+    fn add_transaction_from_iterator() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory
+            .add_transaction_from_iterator(0x2, (0..4).map(|x| x * 2), 0x0)
+            .unwrap();
+        assert_eq!(memory.read(0x1, 6), vec![0xab, 0, 2, 4, 6, 0xab]);
+        assert_eq!(
+            memory.add_transaction_from_iterator(0xe, 0..4, 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            memory.add_transaction_from_iterator(usize::MAX, 0..4, 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            memory.add_transaction_from_iterator(0x2, 0..0, 0x0),
+            Err(MemoryError::EmptyTransaction)
+        );
+        assert_eq!(memory.current_transaction_id(), 1);
+    }
+
+    #[test]
+    fn annotate() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x1, vec![0, 1], 0x1000).unwrap();
+        let plain = memory.clone();
+
+        memory
+            .annotate(TransactionId(1), "instruction", "STORE r0, 0x1")
+            .unwrap();
+        assert_eq!(
+            memory.get_annotation(TransactionId(1), "instruction"),
+            Some("STORE r0, 0x1")
+        );
+        assert_eq!(memory.get_annotation(TransactionId(1), "missing"), None);
+        assert_eq!(memory.get_annotation(TransactionId(0), "instruction"), None);
+        assert_eq!(
+            memory.annotate(TransactionId(0), "instruction", "NOP"),
+            Err(MemoryError::InvalidTransactionId(TransactionId(0)))
+        );
+        assert_eq!(
+            memory.annotate(TransactionId(2), "instruction", "NOP"),
+            Err(MemoryError::InvalidTransactionId(TransactionId(2)))
+        );
+
+        memory.previous_transaction().unwrap();
+        memory.next_transaction().unwrap();
+        assert_eq!(
+            memory.get_annotation(TransactionId(1), "instruction"),
+            Some("STORE r0, 0x1")
+        );
+        assert_eq!(memory, plain);
+    }
+
+    #[test]
+    fn find_address_of_pattern_relative_to() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0x00);
+        memory.add_transaction(0x1, vec![0xeb, 0x10], 0x0).unwrap();
+        memory.add_transaction(0xa, vec![0xeb, 0x20], 0x0).unwrap();
+        let pattern = [Some(0xeb), None];
+
+        assert_eq!(
+            memory.find_address_of_pattern_relative_to(0x1, &pattern),
+            Some(0x1)
+        );
+        assert_eq!(
+            memory.find_address_of_pattern_relative_to(0x4, &pattern),
+            Some(0x1)
+        );
+        assert_eq!(
+            memory.find_address_of_pattern_relative_to(0x7, &pattern),
+            Some(0xa)
+        );
+        assert_eq!(
+            memory.find_address_of_pattern_relative_to(0xf, &pattern),
+            Some(0xa)
+        );
+        assert_eq!(
+            memory.find_address_of_pattern_relative_to(0x4, &[Some(0xeb), Some(0x20)]),
+            Some(0xa)
+        );
+        assert_eq!(
+            memory.find_address_of_pattern_relative_to(0x4, &[Some(0xeb), Some(0x30)]),
+            None
+        );
+        assert_eq!(memory.find_address_of_pattern_relative_to(0x4, &[]), None);
+        assert_eq!(
+            memory.find_address_of_pattern_relative_to(usize::MAX, &pattern),
+            Some(0xa)
+        );
+        assert_eq!(
+            memory.find_address_of_pattern_relative_to(usize::MAX, &[Some(0xeb), Some(0x30)]),
+            None
+        );
+
+        let mut memory = PagedMemory::<16, 1024>::new(0x00);
+        memory.add_transaction(0x10, vec![0xeb, 0x10], 0x0).unwrap();
+        memory
+            .add_transaction(0x3000, vec![0xeb, 0x20], 0x0)
+            .unwrap();
+        assert_eq!(
+            memory.find_address_of_pattern_relative_to(0x1000, &pattern),
+            Some(0x10)
+        );
+        assert_eq!(
+            memory.find_address_of_pattern_relative_to(0x2000, &pattern),
+            Some(0x3000)
+        );
+        assert_eq!(
+            memory.find_address_of_pattern_relative_to(usize::MAX, &pattern),
+            Some(0x3000)
+        );
+    }
+
+    #[test]
+    fn reconstruct_memory_at_transaction() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x1, vec![0, 1, 2], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![3, 4], 0x0).unwrap();
+        let before = memory.clone();
+
+        let mut snapshot = memory
+            .reconstruct_memory_at_transaction(TransactionId(1))
+            .unwrap();
+        assert_eq!(memory, before);
+        assert_eq!(snapshot.read(0x0, 5), vec![0xab, 0, 1, 2, 0xab]);
+        assert_eq!(
+            snapshot.read_transaction_ids(0x0, 16),
+            vec![TransactionId(0); 16]
+        );
+        assert_eq!(snapshot.current_transaction_id(), 0);
+        assert!(snapshot.previous_transaction().is_err());
+
+        snapshot.add_transaction(0x0, vec![5], 0x0).unwrap();
+        assert_eq!(snapshot.read(0x0, 5), vec![5, 0, 1, 2, 0xab]);
+        snapshot.previous_transaction().unwrap();
+        assert_eq!(snapshot.read(0x0, 5), vec![0xab, 0, 1, 2, 0xab]);
+
+        assert_eq!(
+            memory.reconstruct_memory_at_transaction(TransactionId(3)),
+            Err(MemoryError::InvalidTransactionId(TransactionId(3)))
+        );
+    }
+
+    #[test]
+    fn write_up_to_last_byte() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0xf, vec![1], 0x0).unwrap();
+        assert_eq!(memory.read(0xf, 1), vec![1]);
+        memory.add_transaction(0xc, vec![2, 3, 4, 5], 0x0).unwrap();
+        assert_eq!(memory.read(0xc, 4), vec![2, 3, 4, 5]);
+        memory.add_transaction(0x0, vec![6; 16], 0x0).unwrap();
+        assert_eq!(memory.read(0x0, 16), vec![6; 16]);
+
+        assert_eq!(
+            memory.add_transaction(0xf, vec![1, 2], 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            memory.add_transaction(0x0, vec![1; 17], 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        paged_memory.add_transaction(0xf, vec![1], 0x0).unwrap();
+        assert_eq!(paged_memory.read(0xf, 1), vec![1]);
+        assert_eq!(
+            paged_memory.add_transaction(0xf, vec![1, 2], 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+    }
+
+    #[test]
+    fn optimize_transaction_storage() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x1, vec![0, 1], 0x0).unwrap();
+        memory.add_transaction(0x1, vec![2, 3], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![4, 5], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![6, 7], 0x0).unwrap();
+        let before = memory.clone();
+
+        assert_eq!(memory.optimize_transaction_storage(), 2);
+        assert_eq!(memory.optimize_transaction_storage(), 0);
+        assert_eq!(memory, before);
+        let transactions = memory.transactions();
+        assert!(Arc::ptr_eq(
+            transactions[0].data.as_slice().unwrap(),
+            &transactions[1].old_data
+        ));
+        assert!(!Arc::ptr_eq(
+            transactions[1].data.as_slice().unwrap(),
+            &transactions[2].old_data
+        ));
+        assert!(Arc::ptr_eq(
+            transactions[2].data.as_slice().unwrap(),
+            &transactions[3].old_data
+        ));
+
+        memory.move_to_transaction(TransactionId(0)).unwrap();
+        assert_eq!(memory.read(0x0, 4), vec![0xab; 4]);
+        memory.move_to_transaction(TransactionId(4)).unwrap();
+        assert_eq!(memory, before);
+    }
+
+    #[test]
+    fn clone_transaction_log() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x1, vec![0, 1], 0x10).unwrap();
+        memory.add_transaction(0x2, vec![2], 0x14).unwrap();
+        memory.previous_transaction().unwrap();
+
+        let mut log = memory.clone_transaction_log();
+        assert_eq!(log, memory.transactions());
+        assert_eq!(log[1].code_location, 0x14);
+
+        log.clear();
+        memory.annotate(TransactionId(1), "key", "value").unwrap();
+        assert_eq!(memory.transactions().len(), 2);
+        assert_eq!(
+            memory.clone_transaction_log()[0].annotations["key"],
+            "value"
+        );
+    }
+
+    #[test]
+    fn truncate_transaction_log() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x0, vec![0, 1, 2], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![3, 4], 0x0).unwrap();
+        memory.add_transaction(0x3, vec![5], 0x0).unwrap();
+        memory.add_transaction(0x6, vec![6], 0x0).unwrap();
+        memory.previous_transaction().unwrap();
+
+        memory.truncate_transaction_log(2);
+        assert_eq!(memory.transactions().len(), 2);
+        assert_eq!(memory.current_transaction_id(), 1);
+        assert_eq!(memory.read(0x0, 7), vec![0, 1, 3, 5, 0xab, 0xab, 0xab]);
+        assert_eq!(
+            memory.read_transaction_ids(0x0, 7),
+            vec![
+                TransactionId(0),
+                TransactionId(0),
+                TransactionId(0),
+                TransactionId(1),
+                TransactionId(0),
+                TransactionId(0),
+                TransactionId(0),
+            ]
+        );
+
+        memory.previous_transaction().unwrap();
+        assert!(memory.previous_transaction().is_err());
+        assert_eq!(memory.read(0x0, 7), vec![0, 1, 3, 4, 0xab, 0xab, 0xab]);
+        assert_eq!(
+            memory.read_transaction_ids(0x0, 7),
+            vec![TransactionId(0); 7]
+        );
+
+        memory.truncate_transaction_log(1);
+        assert_eq!(memory.current_transaction_id(), 0);
+        assert_eq!(memory.read(0x0, 7), vec![0, 1, 3, 5, 0xab, 0xab, 0xab]);
+        memory.next_transaction().unwrap();
+        assert_eq!(memory.read(0x0, 7), vec![0, 1, 3, 5, 0xab, 0xab, 6]);
+        assert_eq!(memory.read_transaction_ids(0x6, 1), vec![TransactionId(1)]);
+    }
+
+    #[test]
+    fn flat_bytes_roundtrip() {
+        let mut naive_memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        naive_memory
+            .add_transaction(0x2, vec![0, 1, 2], 0x0)
+            .unwrap();
+        paged_memory
+            .add_transaction(0x2, vec![0, 1, 2], 0x0)
+            .unwrap();
+
+        let bytes = paged_memory.to_flat_bytes();
+        assert_eq!(bytes, naive_memory.read(0x0, 16));
+
+        let restored = PagedMemory::<4, 4>::from_flat_bytes(&bytes, 0xab).unwrap();
+        assert_eq!(restored.to_flat_bytes(), bytes);
+        assert_eq!(restored.read(0x0, 16), naive_memory.read(0x0, 16));
+        assert_eq!(
+            restored.pages().filter(|(_, page)| page.is_some()).count(),
+            2
+        );
+        assert_eq!(restored.current_transaction_id(), 0);
+
+        assert_eq!(
+            PagedMemory::<4, 4>::from_flat_bytes(&bytes[1..], 0xab),
+            Err(MemoryError::SizeMismatch {
+                expected: 16,
+                actual: 15
+            })
+        );
+    }
+
+    #[test]
+    fn assert_transaction_id_consistency() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.assert_transaction_id_consistency();
+        memory.add_transaction(0x0, vec![0, 1, 2], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![3, 4], 0x0).unwrap();
+        memory.assert_transaction_id_consistency();
+        memory.previous_transaction().unwrap();
+        memory.assert_transaction_id_consistency();
+        memory.move_to_transaction(TransactionId(0)).unwrap();
+        memory.assert_transaction_id_consistency();
+    }
+
+    #[test]
+    #[should_panic(expected = "address 0x3 holds")]
+    fn assert_transaction_id_consistency_detects_future_ids() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x0, vec![0, 1, 2], 0x0).unwrap();
+        memory.write_transaction_ids(0x3, &[TransactionId(2)]);
+        memory.assert_transaction_id_consistency();
+    }
+
+    #[test]
+    #[should_panic(expected = "2 integrity errors:\naddress 0x3 holds")]
+    fn assert_transaction_id_consistency_reports_every_error() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x0, vec![0, 1, 2], 0x0).unwrap();
+        memory.write_transaction_ids(0x3, &[TransactionId(2), TransactionId(3)]);
+        memory.assert_transaction_id_consistency();
+    }
+
+    #[test]
+    fn validate_integrity() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x0, vec![0, 1, 2], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![3, 4], 0x0).unwrap();
+        assert_eq!(memory.validate_integrity(), Ok(()));
+
+        let mut corrupted = memory.clone();
+        corrupted.set_transaction_idx(3);
+        assert_eq!(
+            corrupted.validate_integrity(),
+            Err(vec![IntegrityError::TransactionIdxOutOfRange {
+                current: 3,
+                len: 2
+            }])
+        );
+
+        let mut corrupted = memory.clone();
+        corrupted.write_transaction_ids(0x5, &[TransactionId(3)]);
+        assert_eq!(
+            corrupted.validate_integrity(),
+            Err(vec![IntegrityError::FutureTransactionId {
+                addr: 0x5,
+                id: TransactionId(3)
+            }])
+        );
+
+        let mut corrupted = memory.clone();
+        corrupted.get_mut_transaction(0).unwrap().old_ids.pop();
+        assert_eq!(
+            corrupted.validate_integrity(),
+            Err(vec![IntegrityError::LengthMismatch {
+                index: 0,
+                data: 3,
+                old_data: 3,
+                old_ids: 2
+            }])
+        );
+
+        let mut corrupted = memory;
+        corrupted.get_mut_transaction(1).unwrap().old_ids[1] = TransactionId(2);
+        assert_eq!(
+            corrupted.validate_integrity(),
+            Err(vec![IntegrityError::DanglingOldId {
+                index: 1,
+                addr: 0x3,
+                id: TransactionId(2)
+            }])
+        );
+    }
+
+    #[test]
+    fn transaction_id_hex() {
+        assert_eq!(format!("{:#010x}", TransactionId(1)), "0x00000001");
+        assert_eq!(format!("{:#010x}", TransactionId(0xbeef)), "0x0000beef");
+        assert_eq!(format!("{:#010X}", TransactionId(0xbeef)), "0x0000BEEF");
+        assert_eq!(format!("{:x}", TransactionId::MAX), "ffffffff");
+        assert_eq!(
+            format!("{:?}", TransactionId::INITIAL),
+            "TransactionId(0x00000000)"
+        );
+        assert_eq!(
+            format!("{:?}", TransactionId(0x2a)),
+            "TransactionId(0x0000002a)"
+        );
+    }
+
+    #[test]
+    fn write_slice_of_words() {
+        let mut memory = NaiveMemory::<4, 8, 32>::new(0xab);
+        memory
+            .write_slice_of_u32_le(0x0, &[0x0403_0201, 0x0807_0605], 0x0)
+            .unwrap();
+        assert_eq!(memory.read(0x0, 8), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(memory.current_transaction_id(), 1);
+
+        memory
+            .write_slice_of_u32_be(0x9, &[0x0102_0304], 0x0)
+            .unwrap();
+        assert_eq!(memory.read(0x8, 6), vec![0xab, 1, 2, 3, 4, 0xab]);
+
+        memory
+            .write_slice_of_u16_le(0x10, &[0x0201, 0x0403], 0x0)
+            .unwrap();
+        memory.write_slice_of_u16_be(0x14, &[0x0506], 0x0).unwrap();
+        assert_eq!(memory.read(0x10, 6), vec![1, 2, 3, 4, 5, 6]);
+
+        memory
+            .write_slice_of_u64_le(0x16, &[0x0807_0605_0403_0201], 0x0)
+            .unwrap();
+        assert_eq!(memory.read(0x16, 8), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        memory
+            .write_slice_of_u64_be(0x18, &[0x0102_0304_0506_0708], 0x0)
+            .unwrap();
+        assert_eq!(memory.read(0x18, 8), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+
+        assert_eq!(
+            memory.write_slice_of_u32_le(0x1e, &[0], 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            memory.write_slice_of_u32_le(0x0, &[], 0x0),
+            Err(MemoryError::EmptyTransaction)
+        );
+    }
+
+    #[test]
+    fn read_string() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x2, b"hi\0".to_vec(), 0x0).unwrap();
+        memory.add_transaction(0x6, vec![0xff, 0x0], 0x0).unwrap();
+        memory.add_transaction(0xc, b"end\0".to_vec(), 0x0).unwrap();
+
+        assert_eq!(memory.read_string(0x2, 16), Ok("hi".to_string()));
+        assert_eq!(memory.read_string(0x2, 3), Ok("hi".to_string()));
+        assert_eq!(memory.read_string(0x4, 1), Ok(String::new()));
+        assert_eq!(
+            memory.read_string(0x2, 2),
+            Err(MemoryError::NullTerminatorNotFound)
+        );
+        assert_eq!(memory.read_string(0x6, 4), Err(MemoryError::InvalidUtf8));
+        assert_eq!(memory.read_bytes_until_null(0x5, 4), Ok(vec![0xab, 0xff]));
+        assert_eq!(memory.read_string(0xc, usize::MAX), Ok("end".to_string()));
+        assert_eq!(
+            memory.read_string(0xd, 0),
+            Err(MemoryError::NullTerminatorNotFound)
+        );
+        assert_eq!(
+            memory.read_bytes_until_null(0x10, 4),
+            Err(MemoryError::AddressOutOfRange)
+        );
+
+        memory.previous_transaction().unwrap();
+        assert_eq!(
+            memory.read_string(0xc, usize::MAX),
+            Err(MemoryError::NullTerminatorNotFound)
+        );
+    }
+
+    #[test]
+    fn relative_addressing() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.write_at_relative(0x8, 0x2, vec![1, 2], 0x0).unwrap();
+        assert_eq!(memory.read(0xa, 2), vec![1, 2]);
+        assert_eq!(memory.read_relative(0x8, 0x1, 3), Ok(vec![0xab, 1, 2]));
+
+        assert_eq!(
+            memory.write_at_relative(usize::MAX, 0x2, vec![1], 0x0),
+            Err(MemoryError::AddressOverflow {
+                base: usize::MAX,
+                offset: 0x2
+            })
+        );
+        assert_eq!(
+            memory.read_relative(0x2, usize::MAX, 1),
+            Err(MemoryError::AddressOverflow {
+                base: 0x2,
+                offset: usize::MAX
+            })
+        );
+        assert_eq!(
+            memory.write_at_relative(0x8, 0x8, vec![1], 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            memory.read_relative(0x8, 0x7, 2),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(memory.current_transaction_id(), 1);
+    }
+
+    #[test]
+    fn write_repeated_pattern() {
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        let mut naive_memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        paged_memory
+            .write_repeated_pattern(0x1, &[0xde, 0xad, 0xbe, 0xef, 0x00], 2, 0x0)
+            .unwrap();
+        naive_memory
+            .write_repeated_pattern(0x1, &[0xde, 0xad, 0xbe, 0xef, 0x00], 2, 0x0)
+            .unwrap();
+        let expected = vec![
+            0xab, 0xde, 0xad, 0xbe, 0xef, 0x00, 0xde, 0xad, 0xbe, 0xef, 0x00, 0xab,
+        ];
+        assert_eq!(paged_memory.read(0x0, 12), expected);
+        assert!(paged_memory.memory_eq(&naive_memory));
+        assert_eq!(paged_memory.current_transaction_id(), 1);
+
+        assert_eq!(
+            paged_memory.write_repeated_pattern(0x0, &[1, 2, 3], 6, 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            paged_memory.write_repeated_pattern(0x0, &[1], usize::MAX, 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            paged_memory.write_repeated_pattern(0x0, &[1, 2, 3], usize::MAX, 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            paged_memory.write_repeated_pattern(0x0, &[], 4, 0x0),
+            Err(MemoryError::EmptyTransaction)
+        );
+        assert_eq!(
+            paged_memory.write_repeated_pattern(0x0, &[1], 0, 0x0),
+            Err(MemoryError::EmptyTransaction)
+        );
+
+        // The pattern period does not divide the chunk size, so chunks start mid-pattern.
+        let mut memory = PagedMemory::<4, 4096>::new(0xab);
+        memory
+            .write_repeated_pattern(0x1, &[1, 2, 3], 5000, 0x0)
+            .unwrap();
+        let expected = [1, 2, 3].repeat(5000);
+        assert_eq!(memory.read(0x1, 15000), expected);
+        assert_eq!(memory.transactions()[0].data.stored_len(), 3);
+        assert_eq!(memory.transactions()[0].new_data(), memory.read(0x1, 15000));
+        memory.previous_transaction().unwrap();
+        assert_eq!(
+            memory.read_at_transaction(0x1000, 8, TransactionId(1)),
+            Ok(expected[0xfff..0x1007].to_vec())
+        );
+    }
+
+    #[test]
+    fn apply_xor_mask() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0x0f);
+        memory.add_transaction(0x2, vec![0xff, 0x00], 0x0).unwrap();
+        memory.apply_xor_mask(0x1, 5, &[0x01, 0x10], 0x0).unwrap();
+        assert_eq!(
+            memory.read(0x0, 7),
+            vec![0x0f, 0x0e, 0xef, 0x01, 0x1f, 0x0e, 0x0f]
+        );
+        assert_eq!(memory.current_transaction_id(), 2);
+
+        memory.previous_transaction().unwrap();
+        assert_eq!(
+            memory.read(0x0, 7),
+            vec![0x0f, 0x0f, 0xff, 0x00, 0x0f, 0x0f, 0x0f]
+        );
+        memory.next_transaction().unwrap();
+
+        assert_eq!(
+            memory.apply_xor_mask(0x0, 4, &[], 0x0),
+            Err(MemoryError::InvalidParams("XOR mask is empty".to_string()))
+        );
+        assert_eq!(
+            memory.apply_xor_mask(0xe, 3, &[0x1], 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            memory.apply_xor_mask(0x0, 0, &[0x1], 0x0),
+            Err(MemoryError::EmptyTransaction)
+        );
+    }
+
+    #[test]
+    fn rebase() {
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        let mut naive_memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        paged_memory
+            .add_transaction(0x2, vec![1, 2, 3], 0x0)
+            .unwrap();
+        naive_memory
+            .add_transaction(0x2, vec![1, 2, 3], 0x0)
+            .unwrap();
+        paged_memory
+            .add_transaction(0x3, vec![0xab, 4], 0x0)
+            .unwrap();
+        naive_memory
+            .add_transaction(0x3, vec![0xab, 4], 0x0)
+            .unwrap();
+
+        paged_memory.rebase(0x00);
+        naive_memory.rebase(0x00);
+
+        let mut expected = vec![0x00; 16];
+        expected[0x2..0x5].copy_from_slice(&[1, 0xab, 4]);
+        assert_eq!(paged_memory.read(0, 16), expected);
+        assert_eq!(naive_memory.read(0, 16), expected);
+
+        paged_memory.previous_transaction().unwrap();
+        naive_memory.previous_transaction().unwrap();
+        expected[0x2..0x5].copy_from_slice(&[1, 2, 3]);
+        assert_eq!(paged_memory.read(0, 16), expected);
+        assert_eq!(naive_memory.read(0, 16), expected);
+
+        paged_memory.previous_transaction().unwrap();
+        naive_memory.previous_transaction().unwrap();
+        assert_eq!(paged_memory.read(0, 16), vec![0x00; 16]);
+        assert_eq!(naive_memory.read(0, 16), vec![0x00; 16]);
+
+        paged_memory.move_to_transaction(TransactionId(2)).unwrap();
+        naive_memory.move_to_transaction(TransactionId(2)).unwrap();
+        expected[0x2..0x5].copy_from_slice(&[1, 0xab, 4]);
+        assert_eq!(paged_memory.read(0, 16), expected);
+        assert_eq!(naive_memory.read(0, 16), expected);
+    }
+
+    #[test]
+    fn rebase_keeps_initialized_bytes() {
+        let mut image = [0xab; 16];
+        image[0x1] = 1;
+        let mut paged_memory = PagedMemory::<4, 4>::from_flat_bytes(&image, 0xab).unwrap();
+        let mut naive_memory = NaiveMemory::<4, 4, 16>::from_slice(&image, 0xab).unwrap();
+        paged_memory.add_transaction(0x2, vec![5], 0x0).unwrap();
+        naive_memory.add_transaction(0x2, vec![5], 0x0).unwrap();
+
+        paged_memory.rebase(0x00);
+        naive_memory.rebase(0x00);
+        paged_memory.previous_transaction().unwrap();
+        naive_memory.previous_transaction().unwrap();
+        assert_eq!(paged_memory.read(0, 16), image);
+        assert_eq!(naive_memory.read(0, 16), image);
+
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        let mut naive_memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        for memory in [
+            &mut paged_memory as &mut dyn InternalMemoryOps<TransactionStorage = Transaction>,
+            &mut naive_memory,
+        ] {
+            assert!(!memory.is_initialized(0x5));
+        }
+        paged_memory
+            .add_transaction(0x5, vec![0xab, 7], 0x0)
+            .unwrap();
+        naive_memory
+            .add_transaction(0x5, vec![0xab, 7], 0x0)
+            .unwrap();
+        paged_memory.add_transaction(0x9, vec![1], 0x0).unwrap();
+        naive_memory.add_transaction(0x9, vec![1], 0x0).unwrap();
+        paged_memory.truncate_transaction_log(1);
+        naive_memory.truncate_transaction_log(1);
+        for memory in [
+            &mut paged_memory as &mut dyn InternalMemoryOps<TransactionStorage = Transaction>,
+            &mut naive_memory,
+        ] {
+            assert!(memory.is_initialized(0x5));
+            assert!(memory.is_initialized(0x6));
+            assert!(!memory.is_initialized(0x4));
+            assert!(!memory.is_initialized(0x7));
+        }
+
+        paged_memory.rebase(0x00);
+        naive_memory.rebase(0x00);
+        let mut expected = vec![0x00; 16];
+        expected[0x5..0x7].copy_from_slice(&[0xab, 7]);
+        expected[0x9] = 1;
+        assert_eq!(paged_memory.read(0, 16), expected);
+        assert_eq!(naive_memory.read(0, 16), expected);
+        paged_memory.previous_transaction().unwrap();
+        naive_memory.previous_transaction().unwrap();
+        expected[0x9] = 0x00;
+        assert_eq!(paged_memory.read(0, 16), expected);
+        assert_eq!(naive_memory.read(0, 16), expected);
+    }
+
+    #[test]
+    fn compare_and_swap() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x4, vec![1, 2], 0x0).unwrap();
+
+        assert_eq!(
+            memory.compare_and_swap(0x4, &[1, 3], vec![5, 6], 0x1),
+            Ok(false)
+        );
+        assert_eq!(memory.transaction_vec_len(), 1);
+        assert_eq!(memory.read(0x4, 2), vec![1, 2]);
+
+        assert_eq!(
+            memory.compare_and_swap(0x4, &[1, 2], vec![5, 6], 0x1),
+            Ok(true)
+        );
+        assert_eq!(memory.transaction_vec_len(), 2);
+        assert_eq!(memory.read(0x4, 2), vec![5, 6]);
+        assert_eq!(memory.transactions()[1].old_data.as_ref(), &[1, 2]);
+
+        assert_eq!(
+            memory.compare_and_swap(0x4, &[5, 6], vec![7], 0x1),
+            Err(MemoryError::SizeMismatch {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            memory.compare_and_swap(0xf, &[0xab, 0xab], vec![7, 8], 0x1),
+            Err(MemoryError::AddressOutOfRange)
+        );
+
+        memory.previous_transaction().unwrap();
+        assert_eq!(
+            memory.compare_and_swap(0x4, &[1, 2], vec![5, 6], 0x1),
+            Err(MemoryError::NotAtLatestTransaction)
+        );
+    }
+
+    #[test]
+    fn transaction_id_constants() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x0, vec![1, 2], 0x0).unwrap();
+        assert!(TransactionId::INITIAL < TransactionId(1));
+        assert!(TransactionId(1) < TransactionId::MAX);
+        assert_eq!(
+            memory.move_to_transaction(TransactionId::MAX),
+            Err(MemoryError::InvalidTransactionId(TransactionId::MAX))
+        );
+        memory.move_to_transaction(TransactionId::INITIAL).unwrap();
+        assert_eq!(memory.current_transaction_id(), 0);
+        assert_eq!(memory.read(0x0, 2), vec![0xab, 0xab]);
+    }
+
+    #[test]
+    fn truncate_future() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x0, vec![1, 2], 0x0).unwrap();
+        memory.add_transaction(0x1, vec![3, 4], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![5, 6], 0x0).unwrap();
+        let truncated_data = Arc::clone(memory.transactions()[2].data.as_slice().unwrap());
+
+        memory.previous_transaction().unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(
+            memory.truncate_future(TransactionId(4)),
+            Err(MemoryError::InvalidTransactionId(TransactionId(4)))
+        );
+        memory.truncate_future(TransactionId(1)).unwrap();
+        assert_eq!(memory.transaction_vec_len(), 1);
+        assert_eq!(Arc::strong_count(&truncated_data), 1);
+        assert_eq!(
+            memory.next_transaction(),
+            Err(MemoryError::NoNextTransaction)
+        );
+
+        memory.add_transaction(0x3, vec![7], 0x0).unwrap();
+        assert_eq!(memory.read(0x0, 4), vec![1, 2, 0xab, 7]);
+        memory.previous_transaction().unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.read(0x0, 4), vec![0xab; 4]);
+        assert_eq!(
+            memory.previous_transaction(),
+            Err(MemoryError::NoPreviousTransaction)
+        );
+
+        memory.move_to_transaction(TransactionId(2)).unwrap();
+        memory.truncate_future(TransactionId(0)).unwrap();
+        assert_eq!(memory.current_transaction_id(), 0);
+        assert_eq!(memory.transaction_vec_len(), 0);
+        assert_eq!(memory.read(0x0, 4), vec![0xab; 4]);
+        assert_eq!(
+            memory.read_transaction_ids(0x0, 4),
+            vec![TransactionId(0); 4]
+        );
+    }
+
+    #[test]
+    fn apply_transaction_inverse() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x0, vec![1, 2], 0x0).unwrap();
+        memory.add_transaction(0x4, vec![3, 4], 0x0).unwrap();
+        memory.add_transaction(0x1, vec![5, 6], 0x0).unwrap();
+
+        memory.apply_transaction_inverse(TransactionId(2)).unwrap();
+        assert_eq!(memory.read(0x0, 6), vec![1, 5, 6, 0xab, 0xab, 0xab]);
+        assert_eq!(memory.current_transaction_id(), 4);
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.read(0x0, 6), vec![1, 5, 6, 0xab, 3, 4]);
+        memory.next_transaction().unwrap();
+
+        assert_eq!(
+            memory.apply_transaction_inverse(TransactionId(1)),
+            Err(MemoryError::ConflictingTransaction(TransactionId(3)))
+        );
+        assert_eq!(
+            memory.apply_transaction_inverse(TransactionId(0)),
+            Err(MemoryError::InvalidTransactionId(TransactionId(0)))
+        );
+        assert_eq!(
+            memory.apply_transaction_inverse(TransactionId(5)),
+            Err(MemoryError::InvalidTransactionId(TransactionId(5)))
+        );
+
+        memory.move_to_transaction(TransactionId(2)).unwrap();
+        assert_eq!(
+            memory.apply_transaction_inverse(TransactionId(3)),
+            Err(MemoryError::InvalidTransactionId(TransactionId(3)))
+        );
+        assert_eq!(
+            memory.apply_transaction_inverse(TransactionId(1)),
+            Err(MemoryError::NotAtLatestTransaction)
+        );
+    }
+
+    #[test]
+    fn clear_future_transactions() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert_eq!(memory.clear_future_transactions(), 0);
+        memory.add_transaction(0x0, vec![1, 2], 0x0).unwrap();
+        memory.add_transaction(0x1, vec![3, 4], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![5, 6], 0x0).unwrap();
+
+        let before = memory.clone();
+        assert_eq!(memory.clear_future_transactions(), 0);
+        assert_eq!(memory, before);
+
+        memory.previous_transaction().unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.clear_future_transactions(), 2);
+        assert_eq!(memory.transaction_vec_len(), 1);
+        assert_eq!(memory.current_transaction_id(), 1);
+        assert_eq!(memory.read(0x0, 4), vec![1, 2, 0xab, 0xab]);
+        assert_eq!(
+            memory.next_transaction(),
+            Err(MemoryError::NoNextTransaction)
+        );
+        memory.add_transaction(0x3, vec![7], 0x0).unwrap();
+        assert_eq!(memory.read(0x0, 4), vec![1, 2, 0xab, 7]);
+    }
+
+    #[test]
+    fn read_range_changed_since() {
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        let mut naive_memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        paged_memory.add_transaction(0x2, vec![1, 2], 0x0).unwrap();
+        naive_memory.add_transaction(0x2, vec![1, 2], 0x0).unwrap();
+        paged_memory.add_transaction(0x3, vec![3, 4], 0x0).unwrap();
+        naive_memory.add_transaction(0x3, vec![3, 4], 0x0).unwrap();
+
+        let expected = [false, false, true, true, true, false];
+        assert_eq!(
+            paged_memory.read_range_changed_since(0x0, 6, TransactionId(0)),
+            expected
+        );
+        assert_eq!(
+            naive_memory.read_range_changed_since(0x0, 6, TransactionId(0)),
+            expected
+        );
+
+        let expected = [false, false, false, true, true, false];
+        assert_eq!(
+            paged_memory.read_range_changed_since(0x0, 6, TransactionId(1)),
+            expected
+        );
+        assert_eq!(
+            naive_memory.read_range_changed_since(0x0, 6, TransactionId(1)),
+            expected
+        );
+
+        assert_eq!(
+            paged_memory.read_range_changed_since(0x8, 8, TransactionId(0)),
+            vec![false; 8]
+        );
+
+        let mask = [true, true, true, false, true, true];
+        let expected = [false, false, true, false, true, false];
+        assert_eq!(
+            paged_memory.read_range_changed_since_masked(0x0, 6, TransactionId(0), &mask),
+            expected
+        );
+        assert_eq!(
+            naive_memory.read_range_changed_since_masked(0x0, 6, TransactionId(0), &mask),
+            expected
+        );
+    }
+
+    #[test]
+    fn transaction_id_conversions() {
+        assert_eq!(TransactionId::from(5), TransactionId(5));
+        assert_eq!(u32::from(TransactionId(7)), 7);
+        assert_eq!(TransactionId::try_from(42usize), Ok(TransactionId(42)));
+        assert_eq!(
+            TransactionId::try_from(u32::MAX as usize),
+            Ok(TransactionId::MAX)
+        );
+        assert_eq!(TransactionId::try_from(u32::MAX as usize + 1), Err(()));
+        assert_eq!(TransactionId(3).to_string(), "3");
+        assert_eq!(
+            MemoryError::InvalidTransactionId(TransactionId(3)).to_string(),
+            "transaction 3 does not exist"
+        );
+    }
+
+    #[test]
+    fn transaction_history_size_bytes() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert_eq!(memory.transaction_history_size_bytes(), 0);
+        memory.add_transaction(0x0, vec![1, 2, 3], 0x0).unwrap();
+        memory.add_transaction(0x8, vec![4], 0x0).unwrap();
+        memory.transactions_mut().shrink_to_fit();
+        let expected = (3 + 3 + 3 * 4) + (1 + 1 + 4) + 2 * std::mem::size_of::<Transaction>();
+        assert_eq!(memory.transaction_history_size_bytes(), expected);
+
+        memory.transactions_mut().reserve_exact(8);
+        let capacity = memory.transactions_mut().capacity();
+        assert_eq!(
+            memory.transaction_history_size_bytes(),
+            expected + (capacity - 2) * std::mem::size_of::<Transaction>()
+        );
+    }
+
+    #[test]
+    fn transaction_id_in_region() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.add_transaction(0x2, vec![1, 2, 3], 0x0).unwrap();
+        memory.add_transaction(0x3, vec![4], 0x0).unwrap();
+        memory.add_transaction(0x8, vec![5, 6], 0x0).unwrap();
+
+        assert_eq!(
+            memory.max_transaction_id_in_region(0x0, 16),
+            TransactionId(3)
+        );
+        assert_eq!(
+            memory.max_transaction_id_in_region(0x2, 4),
+            TransactionId(2)
+        );
+        assert_eq!(
+            memory.min_transaction_id_in_region(0x2, 3),
+            TransactionId(1)
+        );
+        assert_eq!(
+            memory.min_transaction_id_in_region(0x1, 3),
+            TransactionId(0)
+        );
+        assert_eq!(
+            memory.max_transaction_id_in_region(0xc, 4),
+            TransactionId(0)
+        );
+        assert_eq!(
+            memory.max_transaction_id_in_region(0x0, 0),
+            TransactionId(0)
+        );
+        assert_eq!(
+            memory.min_transaction_id_in_region(0x8, 2),
+            TransactionId(3)
+        );
+
+        memory.previous_transaction().unwrap();
+        assert_eq!(
+            memory.max_transaction_id_in_region(0x0, 16),
+            TransactionId(2)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "address range overflows usize")]
+    fn transaction_id_in_region_overflow() {
+        let memory = PagedMemory::<4, 4>::new(0xab);
+        let _ = memory.max_transaction_id_in_region(usize::MAX, 2);
+    }
+
+    #[test]
+    fn transaction_touches() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x4, vec![1, 2, 3], 0x0).unwrap();
+        memory.add_transaction(0x0, vec![4], 0x0).unwrap();
+
+        let id = TransactionId(1);
+        assert!(memory.transaction_touches(id, 0x6, 4));
+        assert!(memory.transaction_touches(id, 0x0, 5));
+        assert!(!memory.transaction_touches(id, 0x7, 2));
+        assert!(!memory.transaction_touches(id, 0x0, 4));
+        assert!(!memory.transaction_touches(id, 0x5, 0));
+        assert!(!memory.transaction_touches(TransactionId::INITIAL, 0x0, 16));
+        assert!(!memory.transaction_touches(TransactionId(3), 0x0, 16));
+        assert!(memory.transaction_touches(TransactionId(2), 0x0, 1));
+        assert!(memory.transaction_touches(id, 0x5, usize::MAX));
+        assert!(!memory.transaction_touches(id, usize::MAX, usize::MAX));
+
+        assert_eq!(
+            memory.transaction_touches_ranges(
+                id,
+                &[(0x6, 4), (0x0, 4), (0x3, 2), (0x5, 0), (0x1, usize::MAX)]
+            ),
+            vec![true, false, true, false, true]
+        );
+        assert_eq!(
+            memory.transaction_touches_ranges(TransactionId(3), &[(0x0, 16)]),
+            vec![false]
+        );
+    }
+
+    #[test]
+    fn byte_frequency_histogram() {
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        let mut naive_memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        paged_memory
+            .add_transaction(0x2, vec![1, 1, 0xab, 2], 0x0)
+            .unwrap();
+        naive_memory
+            .add_transaction(0x2, vec![1, 1, 0xab, 2], 0x0)
+            .unwrap();
+
+        let histogram = paged_memory.byte_frequency_histogram(0x1, 14);
+        assert_eq!(histogram.iter().sum::<u32>(), 14);
+        assert_eq!(histogram[1], 2);
+        assert_eq!(histogram[2], 1);
+        assert_eq!(histogram[0xab], 11);
+        assert_eq!(naive_memory.byte_frequency_histogram(0x1, 14), histogram);
+        assert_eq!(paged_memory.byte_frequency_histogram(0x0, 0), [0; 256]);
+    }
+
+    #[test]
+    fn memory_checksum() {
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        let mut naive_memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        let mut growable_memory = GrowableMemory::new(4, 4, 0xab).unwrap();
+        paged_memory
+            .add_transaction(0x3, vec![1, 2, 3], 0x0)
+            .unwrap();
+        naive_memory
+            .add_transaction(0x3, vec![1, 2, 3], 0x0)
+            .unwrap();
+        growable_memory
+            .add_transaction(0x3, vec![1, 2, 3], 0x0)
+            .unwrap();
+        for (addr, size) in [(0x0, 16), (0x2, 7), (0x8, 8), (0xf, 1)] {
+            let checksum = naive_memory.memory_checksum(addr, size);
+            assert_eq!(paged_memory.memory_checksum(addr, size), checksum);
+            assert_eq!(growable_memory.memory_checksum(addr, size), checksum);
+        }
+    }
+
+    #[test]
+    fn paged_naive_conversion() {
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        paged_memory
+            .add_transaction(0x2, vec![1, 2, 3], 0x0)
+            .unwrap();
+        paged_memory.add_transaction(0xc, vec![0xab], 0x0).unwrap();
+        paged_memory.add_transaction(0x3, vec![4], 0x0).unwrap();
+        paged_memory.previous_transaction().unwrap();
+
+        let mut naive_memory: NaiveMemory<4, 4, 16> = paged_memory.clone().into_naive();
+        assert!(naive_memory.memory_eq(&paged_memory));
+        assert_eq!(
+            naive_memory.read_transaction_ids(0x0, 16),
+            paged_memory.read_transaction_ids(0x0, 16)
+        );
+        assert_eq!(naive_memory.transactions(), paged_memory.transactions());
+        assert_eq!(naive_memory.current_transaction_id(), 2);
+
+        let mut round_trip = naive_memory.clone().into_paged();
+        assert!(round_trip.memory_eq(&paged_memory));
+        assert_eq!(
+            round_trip.read_transaction_ids(0x0, 16),
+            paged_memory.read_transaction_ids(0x0, 16)
+        );
+        assert_eq!(round_trip.transactions(), paged_memory.transactions());
+        let allocated: Vec<_> = round_trip
+            .pages()
+            .filter(|(_, page)| page.is_some())
+            .map(|(idx, _)| idx)
+            .collect();
+        assert_eq!(allocated, vec![0, 1, 3]);
+
+        naive_memory.next_transaction().unwrap();
+        round_trip.next_transaction().unwrap();
+        assert!(round_trip.memory_eq(&naive_memory));
+        assert_eq!(round_trip.read(0x2, 3), vec![1, 4, 3]);
+    }
+
+    #[test]
+    fn bytes_written() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert_eq!(memory.total_bytes_written(), 0);
+        assert_eq!(memory.unique_bytes_written(), 0);
+        memory.add_transaction(0x0, vec![0; 4], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![1; 4], 0x0).unwrap();
+        memory.add_transaction(0xf, vec![2], 0x0).unwrap();
+        assert_eq!(memory.transactions()[1].byte_count(), 4);
+        assert_eq!(memory.total_bytes_written(), 9);
+        assert_eq!(memory.unique_bytes_written(), 7);
+
+        memory.previous_transaction().unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.total_bytes_written(), 9);
+        assert_eq!(memory.unique_bytes_written(), 7);
+        assert_eq!(memory.clear_future_transactions(), 2);
+        assert_eq!(memory.total_bytes_written(), 4);
+        assert_eq!(memory.unique_bytes_written(), 4);
+    }
+
+    #[test]
+    fn validate_transaction() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert_eq!(memory.validate_transaction(0xe, &[1, 2]), Ok(()));
+        assert_eq!(
+            memory.validate_transaction(0xf, &[1, 2]),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            memory.validate_transaction(usize::MAX, &[1]),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            memory.validate_transaction(0x0, &[]),
+            Err(MemoryError::EmptyTransaction)
+        );
+        assert_eq!(
+            memory.add_transaction(0x0, vec![], 0x0),
+            Err(MemoryError::EmptyTransaction)
+        );
+        assert_eq!(
+            memory.add_transaction(usize::MAX, vec![1], 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(memory.transaction_vec_len(), 0);
+
+        memory.add_transaction(0x0, vec![1], 0x0).unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(
+            memory.validate_transaction(0x0, &[1]),
+            Err(MemoryError::NotAtLatestTransaction)
+        );
+        assert_eq!(memory.current_transaction_id(), 0);
+        assert_eq!(memory.transaction_vec_len(), 1);
+    }
+
+    #[test]
+    fn replay() {
+        let mut recorded = NaiveMemory::<4, 4, 16>::new(0xab);
+        recorded.add_transaction(0x0, vec![1, 2], 0x10).unwrap();
+        recorded.add_transaction(0x1, vec![3, 4], 0x20).unwrap();
+        recorded
+            .annotate(TransactionId(2), "source", "test")
+            .unwrap();
+        recorded.add_transaction(0xe, vec![5, 6], 0x30).unwrap();
+        let log = TransactionLog::from_memory(&recorded);
+
+        let mut replayed = PagedMemory::<4, 4>::new(0xab);
+        replayed.replay(&log).unwrap();
+        assert!(replayed.memory_eq(&recorded));
+        assert_eq!(
+            replayed.clone_transaction_log(),
+            recorded.clone_transaction_log()
+        );
+        assert_eq!(
+            replayed.get_annotation(TransactionId(2), "source"),
+            Some("test")
+        );
+
+        let mut partial = PagedMemory::<4, 4>::new(0xab);
+        partial.replay_partial(&log, 2).unwrap();
+        assert_eq!(partial.transaction_vec_len(), 2);
+        assert_eq!(partial.read(0x0, 3), vec![1, 3, 4]);
+
+        let mut small = PagedMemory::<2, 4>::new(0xab);
+        let error = small.replay(&log).unwrap_err();
+        assert_eq!(
+            error,
+            MemoryError::ReplayFailed {
+                index: 2,
+                source: Box::new(MemoryError::AddressOutOfRange)
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "replaying transaction 2 failed: address range exceeds the address space"
+        );
+        assert_eq!(small.transaction_vec_len(), 2);
+    }
+
+    #[test]
+    fn export_region() {
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        let mut naive_memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        paged_memory
+            .add_transaction(0x3, vec![1, 2, 3], 0x0)
+            .unwrap();
+        naive_memory
+            .add_transaction(0x3, vec![1, 2, 3], 0x0)
+            .unwrap();
+
+        let snapshot = paged_memory.export_region(0x2, 4);
+        assert_eq!(snapshot, naive_memory.export_region(0x2, 4));
+        assert_ne!(snapshot, naive_memory.export_region(0x3, 4));
+        assert_eq!(snapshot.base_addr(), 0x2);
+        assert_eq!(snapshot.len(), 4);
+        assert_eq!(snapshot.data(), &[0xab, 1, 2, 3]);
+        assert_eq!(snapshot.read(0x4, 2), &[2, 3]);
+        assert_eq!(
+            snapshot.read_transaction_ids(0x2, 2),
+            &[TransactionId(0), TransactionId(1)]
+        );
+        assert!(snapshot.contains(0x5));
+        assert!(!snapshot.contains(0x6));
+        assert!(!snapshot.contains(0x1));
+
+        paged_memory.previous_transaction().unwrap();
+        assert_eq!(snapshot.read(0x3, 1), &[1]);
+        assert_ne!(snapshot, paged_memory.export_region(0x2, 4));
+    }
+
+    #[test]
+    fn snapshot_content_cmp() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x2, vec![1, 2], 0x0).unwrap();
+        let low = memory.export_region(0x2, 2);
+        let high = memory.export_region(0x8, 2);
+        assert_eq!(low.content_cmp(&high), std::cmp::Ordering::Less);
+        assert_eq!(
+            high.content_cmp(&memory.export_region(0x0, 2)),
+            std::cmp::Ordering::Equal
+        );
+        assert_ne!(high, memory.export_region(0x0, 2));
+    }
+
+    #[test]
+    fn diff_from_snapshot() {
+        let mut original = PagedMemory::<4, 4>::new(0xab);
+        original.add_transaction(0x2, vec![1, 2], 0x0).unwrap();
+        let mut replica = original.clone();
+        let snapshot = original.export_region(0x0, 16);
+        assert!(original.diff_from_snapshot(&snapshot).is_empty());
+
+        original.add_transaction(0x1, vec![3, 1, 4], 0x0).unwrap();
+        original
+            .add_transaction(0x9, vec![5, 0xab, 6], 0x0)
+            .unwrap();
+        let diff = original.diff_from_snapshot(&snapshot);
+        assert_eq!(
+            diff.runs(),
+            &[
+                (0x1, vec![3]),
+                (0x3, vec![4]),
+                (0x9, vec![5]),
+                (0xb, vec![6])
+            ]
+        );
+        assert_eq!(diff.byte_count(), 4);
+
+        let mut bytes = Vec::new();
+        diff.write_to(&mut bytes).unwrap();
+        let decoded = MemoryDiff::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(decoded, diff);
+
+        replica.apply_diff(&decoded).unwrap();
+        assert_eq!(replica.read(0x0, 16), original.read(0x0, 16));
+        replica.apply_diff(&decoded).unwrap();
+        assert_eq!(replica.read(0x0, 16), original.read(0x0, 16));
+        assert!(replica
+            .diff_from_snapshot(&original.export_region(0x0, 16))
+            .is_empty());
+
+        let out_of_range = MemoryDiff::between(0xf, &[0, 0], &[1, 1]);
+        let before = replica.clone();
+        assert_eq!(
+            replica.apply_diff(&MemoryDiff::between(
+                0x0,
+                &[0; 17],
+                &[[1].as_slice(), &[0; 15], &[1]].concat()
+            )),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(
+            replica.apply_diff(&out_of_range),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(replica, before);
+    }
+
+    #[test]
+    fn memory_map() {
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        let mut naive_memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert_eq!(
+            paged_memory.memory_map(),
+            vec![(0x0, 0xf, TransactionId(0))]
+        );
+        for (addr, data) in [(0x2, vec![1; 4]), (0x4, vec![2; 2]), (0x6, vec![3])] {
+            paged_memory
+                .add_transaction(addr, data.clone(), 0x0)
+                .unwrap();
+            naive_memory.add_transaction(addr, data, 0x0).unwrap();
+        }
+        let expected = vec![
+            (0x0, 0x1, TransactionId(0)),
+            (0x2, 0x3, TransactionId(1)),
+            (0x4, 0x5, TransactionId(2)),
+            (0x6, 0x6, TransactionId(3)),
+            (0x7, 0xf, TransactionId(0)),
+        ];
+        assert_eq!(paged_memory.memory_map(), expected);
+        assert_eq!(naive_memory.memory_map(), expected);
+    }
+
+    #[test]
+    fn address_space_size() {
+        assert_eq!(PagedMemory::<8, 4>::new(0).address_space_size(), 32);
+        assert_eq!(NaiveMemory::<8, 4, 32>::new(0).address_space_size(), 32);
+    }
+
+    #[cfg(feature = "protection")]
+    #[test]
+    fn write_protection() {
+        let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+        let mut naive_memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert!(paged_memory.is_writable(0x0));
+        assert!(naive_memory.is_writable(0xf));
+
+        paged_memory.set_readonly(0x4, 4).unwrap();
+        naive_memory.set_readonly(0x4, 4).unwrap();
+        assert!(!paged_memory.is_writable(0x4));
+        assert!(!naive_memory.is_writable(0x7));
+        assert!(paged_memory.is_writable(0x8));
+        assert_eq!(
+            paged_memory.add_transaction(0x2, vec![1, 2, 3], 0x0),
+            Err(MemoryError::WriteProtected(0x4))
+        );
+        assert_eq!(
+            naive_memory.add_transaction(0x2, vec![1, 2, 3], 0x0),
+            Err(MemoryError::WriteProtected(0x4))
+        );
+        paged_memory.add_transaction(0x2, vec![1, 2], 0x0).unwrap();
+        naive_memory.add_transaction(0x2, vec![1, 2], 0x0).unwrap();
+
+        paged_memory.set_writable(0x4, 2).unwrap();
+        naive_memory.set_writable(0x4, 2).unwrap();
+        paged_memory.add_transaction(0x4, vec![3, 4], 0x0).unwrap();
+        naive_memory.add_transaction(0x4, vec![3, 4], 0x0).unwrap();
+        assert_eq!(
+            paged_memory.add_transaction(0x5, vec![5, 6], 0x0),
+            Err(MemoryError::WriteProtected(0x6))
+        );
+        assert!(paged_memory.memory_eq(&naive_memory));
+
+        // Protection only guards new writes, history can still be navigated.
+        paged_memory.set_readonly(0x0, 16).unwrap();
+        paged_memory.previous_transaction().unwrap();
+        assert_eq!(paged_memory.read(0x4, 2), vec![0xab, 0xab]);
+
+        assert_eq!(
+            paged_memory.set_readonly(0xf, 2),
+            Err(MemoryError::AddressOutOfRange)
+        );
+    }
+
+    #[test]
+    fn transaction_getters() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x2, vec![1, 2], 0x1000).unwrap();
+        memory.add_transaction(0x3, vec![3, 4], 0x1004).unwrap();
+        let transaction = memory.current_transaction().unwrap();
+        assert_eq!(transaction.address(), 0x3);
+        assert_eq!(&*transaction.new_data(), &[3, 4]);
+        assert_eq!(transaction.previous_data(), &[2, 0xab]);
+        assert_eq!(
+            transaction.previous_transaction_ids(),
+            &[TransactionId(1), TransactionId(0)]
+        );
+        assert_eq!(transaction.instruction_pointer(), 0x1004);
+        assert!(transaction.annotations().is_empty());
+    }
+
+    #[test]
+    fn transaction_overlaps_and_merge() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x2, vec![1, 2], 0x10).unwrap();
+        memory.add_transaction(0x3, vec![3, 4], 0x20).unwrap();
+        memory.add_transaction(0x5, vec![5], 0x30).unwrap();
+        memory.add_transaction(0x8, vec![6], 0x40).unwrap();
+        let log = memory.clone_transaction_log();
+
+        assert!(log[0].overlaps(&log[1]));
+        assert!(!log[1].overlaps(&log[2]));
+        assert!(!log[2].overlaps(&log[3]));
+
+        let merged = Transaction::merge(log[0].clone(), log[1].clone()).unwrap();
+        assert_eq!(merged.addr, 0x2);
+        assert_eq!(&*merged.data.to_bytes(), &[1, 3, 4]);
+        assert_eq!(merged.old_data.as_ref(), &[0xab; 3]);
+        assert_eq!(merged.old_ids, vec![TransactionId(0); 3]);
+        assert_eq!(merged.code_location, 0x10);
+        assert!(Transaction::merge(log[1].clone(), log[2].clone()).is_some());
+        assert_eq!(Transaction::merge(log[2].clone(), log[3].clone()), None);
+    }
+
+    #[test]
+    fn compact_adjacent_transactions() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x2, vec![1, 2], 0x0).unwrap();
+        memory.add_transaction(0x3, vec![3, 4], 0x0).unwrap();
+        memory.add_transaction(0x5, vec![5], 0x0).unwrap();
+        memory.add_transaction(0x8, vec![6], 0x0).unwrap();
+        memory.add_transaction(0x2, vec![7], 0x0).unwrap();
+        let final_state = memory.read(0x0, 16);
+
+        assert_eq!(memory.compact_adjacent_transactions(), 2);
+        assert_eq!(memory.transaction_vec_len(), 3);
+        assert_eq!(memory.current_transaction_id(), 3);
+        assert_eq!(memory.read(0x0, 16), final_state);
+        let mut expected_ids = vec![TransactionId(0); 16];
+        expected_ids[0x2] = TransactionId(3);
+        expected_ids[0x3..0x6].fill(TransactionId(1));
+        expected_ids[0x8] = TransactionId(2);
+        assert_eq!(memory.read_transaction_ids(0x0, 16), expected_ids);
+        memory.assert_transaction_id_consistency();
+
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.read(0x2, 1), vec![1]);
+        memory.previous_transaction().unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.read(0x0, 16), vec![0xab; 16]);
+
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x2, vec![1, 2], 0x0).unwrap();
+        memory.add_transaction(0x3, vec![3, 4], 0x0).unwrap();
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.compact_adjacent_transactions(), 0);
+        assert_eq!(memory.current_transaction_id(), 1);
+        memory.next_transaction().unwrap();
+        assert_eq!(memory.read(0x2, 3), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn current_transaction() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert_eq!(memory.current_transaction(), None);
+        assert_eq!(memory.last_n_transactions(2).count(), 0);
+        memory.add_transaction(0x1, vec![1], 0x10).unwrap();
+        memory.add_transaction(0x2, vec![2], 0x20).unwrap();
+        memory.add_transaction(0x3, vec![3], 0x30).unwrap();
+        assert_eq!(memory.current_transaction().unwrap().addr, 0x3);
+
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.current_transaction().unwrap().addr, 0x2);
+        let addrs: Vec<_> = memory.last_n_transactions(5).map(|t| t.addr).collect();
+        assert_eq!(addrs, vec![0x2, 0x1]);
+        let addrs: Vec<_> = memory.last_n_transactions(1).map(|t| t.addr).collect();
+        assert_eq!(addrs, vec![0x2]);
+    }
+
+    #[test]
+    fn last_write() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        assert_eq!(memory.last_write_addr(), None);
+        assert_eq!(memory.last_write_size(), None);
+        memory.add_transaction(0x1, vec![1, 2], 0x0).unwrap();
+        memory.add_transaction(0x8, vec![3, 4, 5], 0x0).unwrap();
+        assert_eq!(memory.last_write_addr(), Some(0x8));
+        assert_eq!(memory.last_write_size(), Some(3));
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.last_write_addr(), Some(0x1));
+        assert_eq!(memory.last_write_size(), Some(2));
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.last_write_addr(), None);
+        assert_eq!(memory.last_write_size(), None);
+    }
+
+    #[test]
+    fn transaction_at() {
+        let mut memory = NaiveMemory::<4, 4, 16>::new(0xab);
+        memory.add_transaction(0x1, vec![1], 0x10).unwrap();
+        memory.add_transaction(0x2, vec![2, 3], 0x20).unwrap();
+        memory.previous_transaction().unwrap();
+
+        assert_eq!(memory.transaction_at(TransactionId::INITIAL), None);
+        assert_eq!(
+            memory
+                .transaction_at(TransactionId(1))
+                .unwrap()
+                .code_location,
+            0x10
+        );
+        let transaction = memory.transaction_at(TransactionId(2)).unwrap();
+        assert_eq!(&*transaction.data.to_bytes(), &[2, 3]);
+        assert_eq!(transaction.old_data.as_ref(), &[0xab, 0xab]);
+        assert_eq!(memory.transaction_at(TransactionId(3)), None);
+        assert_eq!(memory.transaction_at(TransactionId::MAX), None);
+    }
+
+    #[test]
+    fn intersect_transaction_ranges() {
+        let transaction = |addr: usize, len: usize| Transaction {
+            addr,
+            data: vec![0; len].into(),
+            old_data: vec![0; len].into(),
+            old_ids: vec![TransactionId(0); len],
+            ..Default::default()
+        };
+        let a = transaction(0x4, 4);
+        let cases = [
+            (transaction(0x6, 4), Some((0x6, 0x7))),
+            (transaction(0x5, 1), Some((0x5, 0x5))),
+            (transaction(0x7, 3), Some((0x7, 0x7))),
+            (transaction(0x1, 4), Some((0x4, 0x4))),
+            (transaction(0x0, 16), Some((0x4, 0x7))),
+            (transaction(0x8, 2), None),
+            (transaction(0x0, 4), None),
+        ];
+        for (b, expected) in cases {
+            assert_eq!(super::intersect_transaction_ranges(&a, &b), expected);
+            assert_eq!(super::intersect_transaction_ranges(&b, &a), expected);
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    enum Operation {
+        Add(usize, Vec<u8>),
+        Previous,
+        Next,
+        Move(u32),
+    }
+
+    fn operation() -> impl proptest::strategy::Strategy<Value = Operation> {
+        use proptest::prelude::*;
+        prop_oneof![
+            (0..16usize, proptest::collection::vec(any::<u8>(), 1..8))
+                .prop_map(|(addr, data)| Operation::Add(addr, data)),
+            Just(Operation::Previous),
+            Just(Operation::Next),
+            (0..24u32).prop_map(Operation::Move),
+        ]
+    }
+
+    fn apply<M: Memory>(memory: &mut M, operation: &Operation) {
+        let _ = match operation {
+            Operation::Add(addr, data) => memory.add_transaction(*addr, data.clone(), 0x0),
+            Operation::Previous => memory.previous_transaction(),
+            Operation::Next => memory.next_transaction(),
+            Operation::Move(id) => memory.move_to_transaction(TransactionId(*id)),
+        };
+    }
+
+    fn assert_undo_redo_roundtrip<M: Memory>(memory: &mut M) {
+        let data = memory.read(0x0, memory.address_space_size());
+        let ids = memory.read_transaction_ids(0x0, memory.address_space_size());
+        let steps = memory.current_transaction_id();
+        for _ in 0..steps {
+            memory.previous_transaction().unwrap();
+        }
+        assert_eq!(memory.current_transaction_id(), 0);
+        assert_eq!(
+            memory.read_transaction_ids(0x0, memory.address_space_size()),
+            vec![TransactionId(0); memory.address_space_size()]
+        );
+        memory.assert_transaction_id_consistency();
+        for _ in 0..steps {
+            memory.next_transaction().unwrap();
+        }
+        assert_eq!(memory.read(0x0, memory.address_space_size()), data);
+        assert_eq!(
+            memory.read_transaction_ids(0x0, memory.address_space_size()),
+            ids
+        );
+        memory.assert_transaction_id_consistency();
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn undo_redo_restores_state(
+            operations in proptest::collection::vec(operation(), 0..32)
+        ) {
+            let mut paged_memory = PagedMemory::<4, 4>::new(0xab);
+            let mut naive_memory = NaiveMemory::<4, 4, 16>::new(0xab);
+            for operation in &operations {
+                apply(&mut paged_memory, operation);
+                apply(&mut naive_memory, operation);
+                proptest::prop_assert!(paged_memory.memory_eq(&naive_memory));
+            }
+            paged_memory.assert_transaction_id_consistency();
+            naive_memory.assert_transaction_id_consistency();
+            assert_undo_redo_roundtrip(&mut paged_memory);
+            assert_undo_redo_roundtrip(&mut naive_memory);
+            proptest::prop_assert!(paged_memory.memory_eq(&naive_memory));
+        }
     }
 }
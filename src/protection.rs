@@ -0,0 +1,43 @@
+use std::ops::Range;
+
+/// Per-byte read-only flags, one bit per address.
+///
+/// The bitset is allocated on the first `set_readonly` call, so memories that never use
+/// protection do not pay for it.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ProtectionMap {
+    readonly: Vec<u64>,
+}
+
+impl ProtectionMap {
+    pub fn set_readonly(&mut self, range: Range<usize>, address_space_size: usize) {
+        let words = address_space_size.div_ceil(64);
+        if self.readonly.len() < words {
+            self.readonly.resize(words, 0);
+        }
+        for addr in range {
+            self.readonly[addr / 64] |= 1 << (addr % 64);
+        }
+    }
+
+    pub fn set_writable(&mut self, range: Range<usize>) {
+        for addr in range {
+            if let Some(word) = self.readonly.get_mut(addr / 64) {
+                *word &= !(1 << (addr % 64));
+            }
+        }
+    }
+
+    pub fn is_readonly(&self, addr: usize) -> bool {
+        self.readonly
+            .get(addr / 64)
+            .is_some_and(|word| word & (1 << (addr % 64)) != 0)
+    }
+
+    pub fn first_readonly(&self, range: Range<usize>) -> Option<usize> {
+        if self.readonly.is_empty() {
+            return None;
+        }
+        range.into_iter().find(|&addr| self.is_readonly(addr))
+    }
+}
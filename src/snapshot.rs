@@ -0,0 +1,148 @@
+use std::cmp::Ordering;
+use std::io::{self, Read, Write};
+
+use crate::transaction_log::{
+    invalid_data, read_bytes, read_u64, read_usize, write_bytes, write_len,
+};
+use crate::TransactionId;
+
+/// Owned copy of a memory region, as returned by [`crate::Memory::export_region`].
+///
+/// Addresses passed to the snapshot are absolute; two snapshots are equal only if they
+/// start at the same base address.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MemorySnapshot {
+    base_addr: usize,
+    data: Vec<u8>,
+    transaction_ids: Vec<TransactionId>,
+}
+
+impl MemorySnapshot {
+    pub(crate) fn new(
+        base_addr: usize,
+        data: Vec<u8>,
+        transaction_ids: Vec<TransactionId>,
+    ) -> Self {
+        debug_assert_eq!(data.len(), transaction_ids.len());
+        Self {
+            base_addr,
+            data,
+            transaction_ids,
+        }
+    }
+
+    pub fn base_addr(&self) -> usize {
+        self.base_addr
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn contains(&self, addr: usize) -> bool {
+        addr >= self.base_addr && addr - self.base_addr < self.data.len()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn transaction_ids(&self) -> &[TransactionId] {
+        &self.transaction_ids
+    }
+
+    /// Orders snapshots by their bytes only, ignoring base addresses and transaction ids.
+    pub fn content_cmp(&self, other: &Self) -> Ordering {
+        self.data.cmp(&other.data)
+    }
+
+    pub fn read(&self, addr: usize, size: usize) -> &[u8] {
+        let offset = self.offset_of(addr, size);
+        &self.data[offset..offset + size]
+    }
+
+    pub fn read_transaction_ids(&self, addr: usize, size: usize) -> &[TransactionId] {
+        let offset = self.offset_of(addr, size);
+        &self.transaction_ids[offset..offset + size]
+    }
+
+    fn offset_of(&self, addr: usize, size: usize) -> usize {
+        assert!(addr >= self.base_addr);
+        let offset = addr - self.base_addr;
+        assert!(offset + size <= self.data.len());
+        offset
+    }
+}
+
+/// Bytes that changed since a [`MemorySnapshot`] was taken, as returned by
+/// [`crate::Memory::diff_from_snapshot`]. Each run is a contiguous block of changed bytes.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct MemoryDiff {
+    runs: Vec<(usize, Vec<u8>)>,
+}
+
+impl MemoryDiff {
+    pub const MAGIC: [u8; 8] = *b"MEMDIFF\0";
+
+    pub(crate) fn between(base_addr: usize, old: &[u8], new: &[u8]) -> Self {
+        debug_assert_eq!(old.len(), new.len());
+        let mut runs: Vec<(usize, Vec<u8>)> = Vec::new();
+        for (offset, (&old_byte, &new_byte)) in old.iter().zip(new).enumerate() {
+            if old_byte == new_byte {
+                continue;
+            }
+            let addr = base_addr + offset;
+            match runs.last_mut() {
+                Some((start, data)) if *start + data.len() == addr => data.push(new_byte),
+                _ => runs.push((addr, vec![new_byte])),
+            }
+        }
+        Self { runs }
+    }
+
+    pub fn runs(&self) -> &[(usize, Vec<u8>)] {
+        &self.runs
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// Total number of changed bytes.
+    pub fn byte_count(&self) -> usize {
+        self.runs.iter().map(|(_, data)| data.len()).sum()
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&Self::MAGIC)?;
+        write_len(&mut writer, self.runs.len())?;
+        for (addr, data) in &self.runs {
+            write_len(&mut writer, *addr)?;
+            write_bytes(&mut writer, data)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != Self::MAGIC {
+            return Err(invalid_data("not a memory diff"));
+        }
+        let count = read_u64(&mut reader)?;
+        let mut runs = Vec::new();
+        for _ in 0..count {
+            let addr = read_usize(&mut reader)?;
+            let data = read_bytes(&mut reader)?;
+            if data.is_empty() || addr.checked_add(data.len()).is_none() {
+                return Err(invalid_data("invalid diff run"));
+            }
+            runs.push((addr, data));
+        }
+        Ok(Self { runs })
+    }
+}
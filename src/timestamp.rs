@@ -0,0 +1,290 @@
+use std::fmt;
+use std::ops::Deref;
+use std::time::Instant;
+
+use crate::internal_memory_ops::{
+    delegate_internal_memory_ops, InternalMemoryOps, TransactionData,
+};
+use crate::{Memory, MemoryError, Transaction, TransactionId};
+
+pub trait Timestamp: Copy + Ord + fmt::Debug {
+    fn now() -> Self;
+}
+
+impl Timestamp for Instant {
+    fn now() -> Self {
+        Instant::now()
+    }
+}
+
+/// Processor cycle counter. Falls back to nanoseconds since the first call on targets
+/// without a readable counter.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct CycleCount(pub u64);
+
+impl Timestamp for CycleCount {
+    fn now() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            // SAFETY: `rdtsc` is available on every x86_64 processor and has no side effects.
+            CycleCount(unsafe { std::arch::x86_64::_rdtsc() })
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        {
+            static START: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+            CycleCount(START.get_or_init(Instant::now).elapsed().as_nanos() as u64)
+        }
+    }
+}
+
+/// A transaction together with the time it was added.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TimestampedTransaction<T: Timestamp> {
+    pub transaction: Transaction,
+    pub timestamp: T,
+}
+
+impl<T: Timestamp> Deref for TimestampedTransaction<T> {
+    type Target = Transaction;
+
+    fn deref(&self) -> &Transaction {
+        &self.transaction
+    }
+}
+
+/// Wraps a memory and records a timestamp for every transaction added through it.
+///
+/// Truncating the log drops the timestamps of the removed transactions. A transaction
+/// produced by [`Memory::compact_adjacent_transactions`] keeps the timestamp of the first
+/// transaction merged into it, like its code location.
+#[derive(Debug, Clone)]
+pub struct TimestampedMemory<M, T> {
+    memory: M,
+    timestamps: Vec<Option<T>>,
+}
+
+impl<M: Memory, T: Timestamp> TimestampedMemory<M, T> {
+    pub fn new(memory: M) -> Self {
+        Self {
+            memory,
+            timestamps: Vec::new(),
+        }
+    }
+
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    pub fn into_inner(self) -> M {
+        self.memory
+    }
+
+    /// Same as [`Memory::add_transaction`], but records `timestamp` instead of `T::now()`.
+    /// Useful when timestamps come from an emulated clock.
+    pub fn add_transaction_at(
+        &mut self,
+        addr: usize,
+        data: Vec<u8>,
+        code_location: usize,
+        timestamp: T,
+    ) -> Result<(), MemoryError> {
+        self.add_transaction_data_at(addr, data.into(), code_location, timestamp)
+    }
+
+    fn add_transaction_data_at(
+        &mut self,
+        addr: usize,
+        data: TransactionData,
+        code_location: usize,
+        timestamp: T,
+    ) -> Result<(), MemoryError> {
+        self.memory
+            .add_transaction_data(addr, data, code_location)?;
+        let idx = self.memory.current_transaction_id() - 1;
+        self.timestamps.resize(idx, None);
+        self.timestamps.push(Some(timestamp));
+        Ok(())
+    }
+
+    /// `None` for [`TransactionId::INITIAL`], unknown ids and transactions that were not
+    /// added through this wrapper.
+    pub fn timestamp(&self, id: TransactionId) -> Option<T> {
+        let idx = (u32::from(id) as usize).checked_sub(1)?;
+        if idx >= self.memory.transaction_vec_len() {
+            return None;
+        }
+        self.timestamps.get(idx).copied().flatten()
+    }
+
+    pub fn timestamped_transaction(&self, id: TransactionId) -> Option<TimestampedTransaction<T>> {
+        Some(TimestampedTransaction {
+            transaction: self.memory.transaction_at(id)?.into_owned(),
+            timestamp: self.timestamp(id)?,
+        })
+    }
+}
+
+impl<M: Memory, T: Timestamp> InternalMemoryOps for TimestampedMemory<M, T> {
+    delegate_internal_memory_ops!(memory: M);
+
+    fn add_transaction_data(
+        &mut self,
+        addr: usize,
+        data: TransactionData,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        self.add_transaction_data_at(addr, data, code_location, T::now())
+    }
+}
+
+impl<M: Memory, T: Timestamp> Memory for TimestampedMemory<M, T> {
+    fn read(&self, addr: usize, size: usize) -> Vec<u8> {
+        self.memory.read(addr, size)
+    }
+
+    fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId> {
+        self.memory.read_transaction_ids(addr, size)
+    }
+
+    fn current_transaction_id(&self) -> usize {
+        self.memory.current_transaction_id()
+    }
+
+    fn address_space_size(&self) -> usize {
+        self.memory.address_space_size()
+    }
+
+    fn clear_transaction_log_preserving_state(&mut self) {
+        self.memory.clear_transaction_log_preserving_state();
+        self.timestamps.clear();
+    }
+
+    fn truncate_transaction_log(&mut self, max_entries: usize) {
+        let removed = self
+            .memory
+            .transaction_vec_len()
+            .saturating_sub(max_entries);
+        self.memory.truncate_transaction_log(max_entries);
+        self.timestamps.drain(..removed.min(self.timestamps.len()));
+    }
+
+    fn compact_adjacent_transactions(&mut self) -> usize {
+        let new_index_of = crate::compacted_indices(
+            self.memory.transactions(),
+            self.memory.current_transaction_id(),
+        );
+        let merged = self.memory.compact_adjacent_transactions();
+        let mut timestamps = Vec::new();
+        for (idx, &new_idx) in new_index_of.iter().enumerate() {
+            if new_idx == timestamps.len() {
+                timestamps.push(self.timestamps.get(idx).copied().flatten());
+            }
+        }
+        self.timestamps = timestamps;
+        merged
+    }
+}
+
+#[cfg(all(test, feature = "naive"))]
+mod tests {
+    use super::*;
+    use crate::NaiveMemory;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn timestamped_memory() {
+        let mut memory =
+            TimestampedMemory::<_, CycleCount>::new(NaiveMemory::<4, 4, 16>::new(0xab));
+        memory
+            .add_transaction_at(0x2, vec![1, 2], 0x0, CycleCount(10))
+            .unwrap();
+        memory
+            .add_transaction_at(0x3, vec![3], 0x0, CycleCount(25))
+            .unwrap();
+        assert_eq!(
+            memory.add_transaction_at(0xf, vec![4, 5], 0x0, CycleCount(30)),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        assert_eq!(memory.timestamp(TransactionId::INITIAL), None);
+        assert_eq!(
+            memory.timestamp(TransactionId::from(1)),
+            Some(CycleCount(10))
+        );
+        assert_eq!(
+            memory.timestamp(TransactionId::from(2)),
+            Some(CycleCount(25))
+        );
+        assert_eq!(memory.timestamp(TransactionId::from(3)), None);
+
+        let transaction = memory
+            .timestamped_transaction(TransactionId::from(2))
+            .unwrap();
+        assert_eq!(transaction.timestamp, CycleCount(25));
+        assert_eq!(transaction.address(), 0x3);
+        assert_eq!(&*transaction.new_data(), &[3]);
+
+        memory.previous_transaction().unwrap();
+        memory.clear_future_transactions();
+        assert_eq!(memory.timestamp(TransactionId::from(2)), None);
+        memory
+            .add_transaction_at(0x0, vec![4], 0x0, CycleCount(40))
+            .unwrap();
+        assert_eq!(
+            memory.timestamp(TransactionId::from(2)),
+            Some(CycleCount(40))
+        );
+        assert_eq!(memory.read(0x0, 4), vec![4, 0xab, 1, 2]);
+    }
+
+    #[test]
+    fn timestamps_follow_log_rewrites() {
+        let mut memory =
+            TimestampedMemory::<_, CycleCount>::new(NaiveMemory::<4, 4, 16>::new(0xab));
+        for (idx, addr) in [0x0, 0x8, 0x9, 0x4].into_iter().enumerate() {
+            memory
+                .add_transaction_at(addr, vec![1], 0x0, CycleCount(idx as u64 * 10))
+                .unwrap();
+        }
+
+        memory.truncate_transaction_log(3);
+        assert_eq!(
+            memory.timestamp(TransactionId::from(1)),
+            Some(CycleCount(10))
+        );
+        assert_eq!(
+            memory.timestamp(TransactionId::from(3)),
+            Some(CycleCount(30))
+        );
+        assert_eq!(memory.timestamp(TransactionId::from(4)), None);
+
+        assert_eq!(memory.compact_adjacent_transactions(), 1);
+        assert_eq!(
+            memory.timestamp(TransactionId::from(1)),
+            Some(CycleCount(10))
+        );
+        assert_eq!(
+            memory.timestamp(TransactionId::from(2)),
+            Some(CycleCount(30))
+        );
+        assert_eq!(memory.timestamp(TransactionId::from(3)), None);
+
+        memory.clear_transaction_log_preserving_state();
+        memory
+            .add_transaction_at(0x0, vec![2], 0x0, CycleCount(40))
+            .unwrap();
+        assert_eq!(
+            memory.timestamp(TransactionId::from(1)),
+            Some(CycleCount(40))
+        );
+    }
+
+    #[test]
+    fn timestamps_are_monotonic() {
+        let mut memory = TimestampedMemory::<_, Instant>::new(NaiveMemory::<4, 4, 16>::new(0xab));
+        memory.add_transaction(0x0, vec![1], 0x0).unwrap();
+        memory.add_transaction(0x1, vec![2], 0x0).unwrap();
+        assert!(
+            memory.timestamp(TransactionId::from(1)) <= memory.timestamp(TransactionId::from(2))
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt;
+
+use crate::internal_memory_ops::{
+    delegate_internal_memory_ops, InternalMemoryOps, TransactionData,
+};
+use crate::{Memory, MemoryError, TransactionId};
+
+/// Called with `(addr, size, data, internal)`. `internal` is `true` for the read that
+/// captures `old_data` inside `add_transaction`, and `false` for reads made by the caller.
+type ReadCallback = Box<dyn Fn(usize, usize, &[u8], bool)>;
+
+/// Wraps a memory and reports every read to the registered callbacks.
+pub struct TracingMemory<M> {
+    memory: M,
+    read_callbacks: Vec<ReadCallback>,
+}
+
+impl<M: Memory> TracingMemory<M> {
+    pub fn new(memory: M) -> Self {
+        Self {
+            memory,
+            read_callbacks: Vec::new(),
+        }
+    }
+
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    pub fn into_inner(self) -> M {
+        self.memory
+    }
+
+    pub fn on_read(&mut self, callback: ReadCallback) {
+        self.read_callbacks.push(callback);
+    }
+
+    fn report_read(&self, addr: usize, data: &[u8], internal: bool) {
+        for callback in &self.read_callbacks {
+            callback(addr, data.len(), data, internal);
+        }
+    }
+}
+
+impl<M: fmt::Debug> fmt::Debug for TracingMemory<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TracingMemory")
+            .field("memory", &self.memory)
+            .field("read_callbacks", &self.read_callbacks.len())
+            .finish()
+    }
+}
+
+impl<M: Memory> InternalMemoryOps for TracingMemory<M> {
+    delegate_internal_memory_ops!(memory: M);
+
+    fn add_transaction_data(
+        &mut self,
+        addr: usize,
+        data: TransactionData,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        self.memory
+            .add_transaction_data(addr, data, code_location)?;
+        if let Some(transaction) = self.memory.current_transaction() {
+            self.report_read(addr, transaction.previous_data(), true);
+        }
+        Ok(())
+    }
+}
+
+impl<M: Memory> Memory for TracingMemory<M> {
+    fn read(&self, addr: usize, size: usize) -> Vec<u8> {
+        let data = self.memory.read(addr, size);
+        self.report_read(addr, &data, false);
+        data
+    }
+
+    fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId> {
+        self.memory.read_transaction_ids(addr, size)
+    }
+
+    fn current_transaction_id(&self) -> usize {
+        self.memory.current_transaction_id()
+    }
+
+    fn address_space_size(&self) -> usize {
+        self.memory.address_space_size()
+    }
+}
+
+#[cfg(all(test, feature = "naive"))]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::NaiveMemory;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn on_read() {
+        let mut memory = TracingMemory::new(NaiveMemory::<4, 4, 16>::new(0xab));
+        let reads = Rc::new(RefCell::new(Vec::new()));
+        let recorded_reads = Rc::clone(&reads);
+        memory.on_read(Box::new(move |addr, size, data, internal| {
+            recorded_reads
+                .borrow_mut()
+                .push((addr, size, data.to_vec(), internal));
+        }));
+
+        memory.add_transaction(0x2, vec![1, 2], 0x0).unwrap();
+        assert_eq!(memory.read(0x1, 3), vec![0xab, 1, 2]);
+        memory.add_transaction(0x3, vec![3], 0x0).unwrap();
+        assert_eq!(
+            memory.add_transaction(0xf, vec![4, 5], 0x0),
+            Err(MemoryError::AddressOutOfRange)
+        );
+
+        assert_eq!(
+            *reads.borrow(),
+            vec![
+                (0x2, 2, vec![0xab, 0xab], true),
+                (0x1, 3, vec![0xab, 1, 2], false),
+                (0x3, 1, vec![2], true),
+            ]
+        );
+    }
+}
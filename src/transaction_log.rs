@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+use crate::{Memory, MemoryError, Transaction, TransactionId};
+
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct TransactionLog {
+    transactions: Vec<Transaction>,
+}
+
+impl TransactionLog {
+    pub const MAGIC: [u8; 8] = *b"MEMTXLOG";
+    pub const VERSION: u8 = 1;
+
+    pub fn new(transactions: Vec<Transaction>) -> Self {
+        Self { transactions }
+    }
+
+    pub fn from_memory<M: Memory>(memory: &M) -> Self {
+        Self::new(memory.clone_transaction_log())
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    pub fn into_transactions(self) -> Vec<Transaction> {
+        self.transactions
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&Self::MAGIC)?;
+        writer.write_all(&[Self::VERSION])?;
+        write_len(&mut writer, self.transactions.len())?;
+        for transaction in &self.transactions {
+            write_transaction(&mut writer, transaction)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != Self::MAGIC {
+            return Err(invalid_data("not a transaction log"));
+        }
+        let mut version = [0];
+        reader.read_exact(&mut version)?;
+        if version[0] != Self::VERSION {
+            return Err(invalid_data("unsupported transaction log version"));
+        }
+        let count = read_u64(&mut reader)?;
+        let mut transactions = Vec::new();
+        for _ in 0..count {
+            transactions.push(read_transaction(&mut reader)?);
+        }
+        Ok(Self { transactions })
+    }
+}
+
+impl From<Vec<Transaction>> for TransactionLog {
+    fn from(transactions: Vec<Transaction>) -> Self {
+        Self::new(transactions)
+    }
+}
+
+impl Transaction {
+    /// Encodes the transaction in the same format as a single `TransactionLog` entry.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_transaction(&mut bytes, self).expect("writing to a Vec cannot fail");
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for Transaction {
+    type Error = MemoryError;
+
+    fn try_from(mut bytes: &[u8]) -> Result<Self, Self::Error> {
+        let transaction = read_transaction(&mut bytes).map_err(|error| {
+            match error
+                .get_ref()
+                .and_then(|source| source.downcast_ref::<MemoryError>())
+            {
+                Some(error) => error.clone(),
+                None => MemoryError::Io(error.kind()),
+            }
+        })?;
+        if !bytes.is_empty() {
+            return Err(MemoryError::Io(io::ErrorKind::InvalidData));
+        }
+        Ok(transaction)
+    }
+}
+
+pub(crate) fn write_transaction(
+    writer: &mut impl Write,
+    transaction: &Transaction,
+) -> io::Result<()> {
+    write_len(writer, transaction.addr)?;
+    write_len(writer, transaction.code_location)?;
+    write_bytes(writer, &transaction.data.to_bytes())?;
+    write_bytes(writer, &transaction.old_data)?;
+    write_len(writer, transaction.old_ids.len())?;
+    for id in &transaction.old_ids {
+        writer.write_all(&id.0.to_le_bytes())?;
+    }
+    let mut annotations: Vec<_> = transaction.annotations.iter().collect();
+    annotations.sort();
+    write_len(writer, annotations.len())?;
+    for (key, value) in annotations {
+        write_bytes(writer, key.as_bytes())?;
+        write_bytes(writer, value.as_bytes())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_transaction(reader: &mut impl Read) -> io::Result<Transaction> {
+    let addr = read_usize(reader)?;
+    let code_location = read_usize(reader)?;
+    let data = read_bytes(reader)?;
+    let old_data = read_bytes(reader)?;
+    let old_ids_len = read_u64(reader)?;
+    let mut old_ids = Vec::new();
+    for _ in 0..old_ids_len {
+        let mut id = [0; 4];
+        reader.read_exact(&mut id)?;
+        old_ids.push(TransactionId(u32::from_le_bytes(id)));
+    }
+    let annotations_len = read_u64(reader)?;
+    let mut annotations = HashMap::new();
+    for _ in 0..annotations_len {
+        let key = read_string(reader)?;
+        let value = read_string(reader)?;
+        annotations.insert(key, value);
+    }
+    if old_data.len() != data.len() {
+        return Err(invalid_transaction(MemoryError::SizeMismatch {
+            expected: data.len(),
+            actual: old_data.len(),
+        }));
+    }
+    if old_ids.len() != data.len() {
+        return Err(invalid_transaction(MemoryError::SizeMismatch {
+            expected: data.len(),
+            actual: old_ids.len(),
+        }));
+    }
+    if addr.checked_add(data.len()).is_none() {
+        return Err(invalid_transaction(MemoryError::AddressOutOfRange));
+    }
+    Ok(Transaction {
+        addr,
+        data: data.into(),
+        old_data: old_data.into(),
+        old_ids,
+        code_location,
+        annotations,
+    })
+}
+
+fn invalid_transaction(error: MemoryError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn write_len(writer: &mut impl Write, len: usize) -> io::Result<()> {
+    writer.write_all(&(len as u64).to_le_bytes())
+}
+
+pub(crate) fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    write_len(writer, bytes.len())?;
+    writer.write_all(bytes)
+}
+
+pub(crate) fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut value = [0; 8];
+    reader.read_exact(&mut value)?;
+    Ok(u64::from_le_bytes(value))
+}
+
+pub(crate) fn read_usize(reader: &mut impl Read) -> io::Result<usize> {
+    usize::try_from(read_u64(reader)?).map_err(|_| invalid_data("value does not fit into usize"))
+}
+
+pub(crate) fn read_bytes(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_u64(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    String::from_utf8(read_bytes(reader)?).map_err(|_| invalid_data("annotation is not UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PagedMemory;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn roundtrip_and_replay() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.add_transaction(0x1, vec![0, 1, 2], 0x10).unwrap();
+        memory.add_transaction(0x3, vec![3, 4, 5, 6], 0x14).unwrap();
+        memory
+            .annotate(TransactionId(2), "instruction", "STORE")
+            .unwrap();
+
+        let log = TransactionLog::from_memory(&memory);
+        let mut encoded = Vec::new();
+        log.write_to(&mut encoded).unwrap();
+        assert_eq!(&encoded[..8], b"MEMTXLOG");
+        assert_eq!(encoded[8], TransactionLog::VERSION);
+
+        let decoded = TransactionLog::read_from(encoded.as_slice()).unwrap();
+        assert_eq!(decoded, log);
+        assert_eq!(
+            decoded.transactions()[1].annotations["instruction"],
+            "STORE"
+        );
+
+        let mut replayed = PagedMemory::<4, 4>::new(0xab);
+        for transaction in decoded.transactions() {
+            replayed
+                .add_transaction(
+                    transaction.addr,
+                    transaction.new_data().into_owned(),
+                    transaction.code_location,
+                )
+                .unwrap();
+        }
+        assert!(replayed.memory_eq(&memory));
+        assert_eq!(
+            replayed.clone_transaction_log(),
+            memory.clone_transaction_log()
+        );
+    }
+
+    #[test]
+    fn reject_invalid_input() {
+        let mut encoded = Vec::new();
+        TransactionLog::default().write_to(&mut encoded).unwrap();
+        assert_eq!(
+            TransactionLog::read_from(encoded.as_slice()).unwrap(),
+            TransactionLog::default()
+        );
+
+        let mut bad_version = encoded.clone();
+        bad_version[8] = TransactionLog::VERSION + 1;
+        assert_eq!(
+            TransactionLog::read_from(bad_version.as_slice())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let mut bad_magic = encoded.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(
+            TransactionLog::read_from(bad_magic.as_slice())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        assert_eq!(
+            TransactionLog::read_from(&encoded[..12])
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.add_transaction(0x1, vec![0, 1, 2], 0x10).unwrap();
+        let mut transactions = memory.clone_transaction_log();
+        transactions[0].old_data = vec![0xab; 2].into();
+        let mut mismatched = Vec::new();
+        TransactionLog::new(transactions)
+            .write_to(&mut mismatched)
+            .unwrap();
+        let error = TransactionLog::read_from(mismatched.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.into_inner().unwrap().downcast_ref::<MemoryError>(),
+            Some(&MemoryError::SizeMismatch {
+                expected: 3,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn transaction_from_bytes() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        memory.add_transaction(0x1, vec![0, 1, 2], 0x10).unwrap();
+        memory.add_transaction(0x3, vec![3, 4], 0x14).unwrap();
+        memory.annotate(TransactionId(2), "note", "second").unwrap();
+        let transaction = memory.current_transaction().unwrap().into_owned();
+
+        let bytes = transaction.to_bytes();
+        let decoded = Transaction::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(decoded, transaction);
+        assert_eq!(decoded.annotations, transaction.annotations);
+
+        assert_eq!(
+            Transaction::try_from(&bytes[..bytes.len() - 1]),
+            Err(MemoryError::Io(io::ErrorKind::UnexpectedEof))
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Transaction::try_from(trailing.as_slice()),
+            Err(MemoryError::Io(io::ErrorKind::InvalidData))
+        );
+
+        let overflowing = Transaction {
+            addr: usize::MAX,
+            ..transaction.clone()
+        };
+        assert_eq!(
+            Transaction::try_from(overflowing.to_bytes().as_slice()),
+            Err(MemoryError::AddressOutOfRange)
+        );
+        let mismatched = Transaction {
+            old_ids: vec![],
+            ..transaction
+        };
+        assert_eq!(
+            Transaction::try_from(mismatched.to_bytes().as_slice()),
+            Err(MemoryError::SizeMismatch {
+                expected: 2,
+                actual: 0
+            })
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+use crate::{MemoryError, Transaction, TransactionId};
+
+/// How a memory keeps the entries of its transaction log.
+///
+/// [`Transaction`] keeps any transaction on the heap. [`SmallTransaction`] keeps small
+/// transactions inline, so a log with reserved capacity does not allocate per entry.
+pub trait TransactionStorage: Default + Clone + fmt::Debug + Eq + Ord + Hash {
+    /// Largest transaction, in bytes, that an entry can hold.
+    const MAX_BYTES: usize;
+
+    /// Fails if the transaction does not fit into an entry.
+    fn store(transaction: Transaction) -> Result<Self, MemoryError>;
+
+    fn load(&self) -> Cow<'_, Transaction>;
+
+    /// First address written by the stored transaction.
+    fn address(&self) -> usize;
+
+    fn byte_count(&self) -> usize;
+
+    /// Value of annotation `key` of the stored transaction.
+    fn annotation(&self, key: &str) -> Option<&str>;
+
+    /// Heap bytes held by the entry, not counting annotations.
+    fn heap_size(&self) -> usize;
+
+    /// Changes the stored transaction with `f`. Fails and leaves the entry unchanged if the
+    /// result does not fit.
+    fn update(&mut self, f: impl FnOnce(&mut Transaction)) -> Result<(), MemoryError> {
+        let mut transaction = self.load().into_owned();
+        f(&mut transaction);
+        *self = Self::store(transaction)?;
+        Ok(())
+    }
+}
+
+impl TransactionStorage for Transaction {
+    const MAX_BYTES: usize = usize::MAX;
+
+    fn store(transaction: Transaction) -> Result<Self, MemoryError> {
+        Ok(transaction)
+    }
+
+    fn load(&self) -> Cow<'_, Transaction> {
+        Cow::Borrowed(self)
+    }
+
+    fn address(&self) -> usize {
+        self.addr
+    }
+
+    fn byte_count(&self) -> usize {
+        self.data.len()
+    }
+
+    fn annotation(&self, key: &str) -> Option<&str> {
+        self.annotations.get(key).map(String::as_str)
+    }
+
+    /// Fills count only the bytes actually stored.
+    fn heap_size(&self) -> usize {
+        self.data.stored_len()
+            + self.old_data.len()
+            + self.old_ids.len() * std::mem::size_of::<TransactionId>()
+    }
+
+    fn update(&mut self, f: impl FnOnce(&mut Transaction)) -> Result<(), MemoryError> {
+        f(self);
+        Ok(())
+    }
+}
+
+/// Log entry that keeps the data, old data and old ids of a transaction of at most
+/// `MAX_SIZE` bytes inline.
+///
+/// Longer writes fail with [`MemoryError::TransactionTooLarge`]. Annotations are not kept,
+/// so annotating fails with [`MemoryError::AnnotationsUnsupported`].
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SmallTransaction<const MAX_SIZE: usize> {
+    addr: usize,
+    len: usize,
+    data: [u8; MAX_SIZE],
+    old_data: [u8; MAX_SIZE],
+    old_ids: [TransactionId; MAX_SIZE],
+    code_location: usize,
+}
+
+impl<const MAX_SIZE: usize> Default for SmallTransaction<MAX_SIZE> {
+    fn default() -> Self {
+        Self {
+            addr: 0,
+            len: 0,
+            data: [0; MAX_SIZE],
+            old_data: [0; MAX_SIZE],
+            old_ids: [TransactionId::INITIAL; MAX_SIZE],
+            code_location: 0,
+        }
+    }
+}
+
+impl<const MAX_SIZE: usize> TransactionStorage for SmallTransaction<MAX_SIZE> {
+    const MAX_BYTES: usize = MAX_SIZE;
+
+    fn store(transaction: Transaction) -> Result<Self, MemoryError> {
+        let len = transaction.byte_count();
+        if len > MAX_SIZE {
+            return Err(MemoryError::TransactionTooLarge {
+                size: len,
+                max_size: MAX_SIZE,
+            });
+        }
+        if !transaction.annotations.is_empty() {
+            return Err(MemoryError::AnnotationsUnsupported);
+        }
+        let mut entry = Self {
+            addr: transaction.addr,
+            len,
+            code_location: transaction.code_location,
+            ..Self::default()
+        };
+        transaction.data.copy_to(0, &mut entry.data[..len]);
+        entry.old_data[..len].copy_from_slice(&transaction.old_data);
+        entry.old_ids[..len].copy_from_slice(&transaction.old_ids);
+        Ok(entry)
+    }
+
+    fn load(&self) -> Cow<'_, Transaction> {
+        Cow::Owned(Transaction {
+            addr: self.addr,
+            data: self.data[..self.len].into(),
+            old_data: self.old_data[..self.len].into(),
+            old_ids: self.old_ids[..self.len].to_vec(),
+            code_location: self.code_location,
+            annotations: HashMap::new(),
+        })
+    }
+
+    fn address(&self) -> usize {
+        self.addr
+    }
+
+    fn byte_count(&self) -> usize {
+        self.len
+    }
+
+    fn annotation(&self, _key: &str) -> Option<&str> {
+        None
+    }
+
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_memory_ops::InternalMemoryOps;
+    use crate::{BackendMemory, Memory, MemoryBackend};
+    use pretty_assertions::assert_eq;
+
+    #[derive(Debug)]
+    struct ArrayBackend {
+        data: [u8; 16],
+        ids: [TransactionId; 16],
+    }
+
+    impl MemoryBackend for ArrayBackend {
+        fn address_space_size(&self) -> usize {
+            self.data.len()
+        }
+
+        fn default_value(&self) -> u8 {
+            0xab
+        }
+
+        fn read(&self, addr: usize, size: usize) -> Vec<u8> {
+            self.data[addr..addr + size].to_vec()
+        }
+
+        fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId> {
+            self.ids[addr..addr + size].to_vec()
+        }
+
+        fn write_data(&mut self, addr: usize, data: &[u8]) {
+            self.data[addr..addr + data.len()].copy_from_slice(data);
+        }
+
+        fn write_transaction_ids(&mut self, addr: usize, transaction_ids: &[TransactionId]) {
+            self.ids[addr..addr + transaction_ids.len()].copy_from_slice(transaction_ids);
+        }
+
+        fn is_initialized(&self, _addr: usize) -> bool {
+            false
+        }
+
+        fn mark_initialized(&mut self, _addr: usize, _size: usize) {}
+
+        fn replace_default_value(&mut self, _new_default: u8) {}
+    }
+
+    #[test]
+    fn small_transactions() {
+        let mut memory = BackendMemory::<_, SmallTransaction<4>>::with_storage(ArrayBackend {
+            data: [0xab; 16],
+            ids: [TransactionId::INITIAL; 16],
+        });
+        memory.add_transaction(0x2, vec![1, 2, 3], 0x10).unwrap();
+        memory.write_fill(0x4, 4, 7, 0x14).unwrap();
+        assert_eq!(
+            memory.add_transaction(0x0, vec![0; 5], 0x18),
+            Err(MemoryError::TransactionTooLarge {
+                size: 5,
+                max_size: 4
+            })
+        );
+        assert_eq!(
+            memory.annotate(TransactionId::from(1), "key", "value"),
+            Err(MemoryError::AnnotationsUnsupported)
+        );
+        assert_eq!(memory.transaction_vec_len(), 2);
+
+        let transaction = memory.current_transaction().unwrap();
+        assert_eq!(transaction.address(), 0x4);
+        assert_eq!(&*transaction.new_data(), &[7; 4]);
+        assert_eq!(transaction.previous_data(), &[3, 0xab, 0xab, 0xab]);
+        assert_eq!(transaction.instruction_pointer(), 0x14);
+
+        // The merged write would not fit into one entry.
+        assert_eq!(memory.compact_adjacent_transactions(), 0);
+
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.read(0x2, 4), vec![1, 2, 3, 0xab]);
+        memory.previous_transaction().unwrap();
+        assert_eq!(memory.read(0x0, 16), vec![0xab; 16]);
+        memory.move_to_transaction(TransactionId::from(2)).unwrap();
+        assert_eq!(memory.read(0x2, 6), vec![1, 2, 7, 7, 7, 7]);
+        assert_eq!(memory.validate_integrity(), Ok(()));
+    }
+}
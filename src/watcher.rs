@@ -0,0 +1,209 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::internal_memory_ops::{
+    delegate_internal_memory_ops, InternalMemoryOps, TransactionData,
+};
+use crate::{Memory, MemoryError, TransactionId};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct WatchId(usize);
+
+type WatchCallback = Box<dyn Fn(usize, u8, u8)>;
+
+struct Watch {
+    id: WatchId,
+    range: RangeInclusive<usize>,
+    callback: WatchCallback,
+}
+
+/// Calls the registered callbacks for every watched byte changed by [`Memory::add_transaction`]
+/// (and the methods built on it) or by [`Memory::rebase`]. Undo, redo and history rewrites
+/// don't fire callbacks.
+pub struct MemoryWatcher<M> {
+    memory: M,
+    watches: Vec<Watch>,
+    next_watch_id: usize,
+}
+
+impl<M: Memory> MemoryWatcher<M> {
+    pub fn new(memory: M) -> Self {
+        Self {
+            memory,
+            watches: Vec::new(),
+            next_watch_id: 0,
+        }
+    }
+
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    pub fn into_inner(self) -> M {
+        self.memory
+    }
+
+    pub fn watch(&mut self, range: RangeInclusive<usize>, callback: WatchCallback) -> WatchId {
+        let id = WatchId(self.next_watch_id);
+        self.next_watch_id += 1;
+        self.watches.push(Watch {
+            id,
+            range,
+            callback,
+        });
+        id
+    }
+
+    pub fn remove_watch(&mut self, id: WatchId) -> bool {
+        let len = self.watches.len();
+        self.watches.retain(|watch| watch.id != id);
+        self.watches.len() != len
+    }
+
+    fn notify(&self, addr: usize, old_data: &[u8], new_data: &[u8]) {
+        let changes = old_data
+            .iter()
+            .zip(new_data)
+            .enumerate()
+            .filter(|(_, (old_value, new_value))| old_value != new_value);
+        for (offset, (old_value, new_value)) in changes {
+            let changed_addr = addr + offset;
+            for watch in &self.watches {
+                if watch.range.contains(&changed_addr) {
+                    (watch.callback)(changed_addr, *old_value, *new_value);
+                }
+            }
+        }
+    }
+}
+
+impl<M: fmt::Debug> fmt::Debug for MemoryWatcher<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryWatcher")
+            .field("memory", &self.memory)
+            .field(
+                "watches",
+                &self
+                    .watches
+                    .iter()
+                    .map(|watch| (watch.id, &watch.range))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl<M: Memory> InternalMemoryOps for MemoryWatcher<M> {
+    delegate_internal_memory_ops!(memory: M);
+
+    fn add_transaction_data(
+        &mut self,
+        addr: usize,
+        data: TransactionData,
+        code_location: usize,
+    ) -> Result<(), MemoryError> {
+        self.memory
+            .add_transaction_data(addr, data, code_location)?;
+        if self.watches.is_empty() {
+            return Ok(());
+        }
+        let transaction = self.memory.current_transaction().unwrap();
+        self.notify(addr, transaction.previous_data(), &transaction.new_data());
+        Ok(())
+    }
+}
+
+impl<M: Memory> Memory for MemoryWatcher<M> {
+    fn read(&self, addr: usize, size: usize) -> Vec<u8> {
+        self.memory.read(addr, size)
+    }
+
+    fn read_transaction_ids(&self, addr: usize, size: usize) -> Vec<TransactionId> {
+        self.memory.read_transaction_ids(addr, size)
+    }
+
+    fn current_transaction_id(&self) -> usize {
+        self.memory.current_transaction_id()
+    }
+
+    fn address_space_size(&self) -> usize {
+        self.memory.address_space_size()
+    }
+
+    fn rebase(&mut self, new_default: u8) {
+        let last_addr = self.memory.address_space_size() - 1;
+        let start = self.watches.iter().map(|watch| *watch.range.start()).min();
+        let end = self.watches.iter().map(|watch| *watch.range.end()).max();
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) if start <= end.min(last_addr) => (start, end.min(last_addr)),
+            _ => return self.memory.rebase(new_default),
+        };
+        let size = end - start + 1;
+        let old_data = self.memory.read(start, size);
+        self.memory.rebase(new_default);
+        self.notify(start, &old_data, &self.memory.read(start, size));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PagedMemory;
+    use pretty_assertions::assert_eq;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn watch() {
+        let mut memory = MemoryWatcher::new(PagedMemory::<4, 4>::new(0xab));
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let watch_calls = Rc::clone(&calls);
+        let id = memory.watch(
+            0x2..=0x5,
+            Box::new(move |addr, old_value, new_value| {
+                watch_calls.borrow_mut().push((addr, old_value, new_value))
+            }),
+        );
+
+        memory
+            .add_transaction(0x0, vec![0, 1, 0xab, 3], 0x0)
+            .unwrap();
+        assert_eq!(*calls.borrow(), vec![(0x3, 0xab, 3)]);
+
+        memory.add_transaction(0x3, vec![4, 5, 6, 7], 0x0).unwrap();
+        assert_eq!(
+            *calls.borrow(),
+            vec![(0x3, 0xab, 3), (0x3, 3, 4), (0x4, 0xab, 5), (0x5, 0xab, 6)]
+        );
+
+        memory.previous_transaction().unwrap();
+        memory.next_transaction().unwrap();
+        assert!(memory.add_transaction(0xf, vec![0, 1], 0x0).is_err());
+        assert_eq!(calls.borrow().len(), 4);
+
+        assert!(memory.remove_watch(id));
+        assert!(!memory.remove_watch(id));
+        memory.add_transaction(0x2, vec![8, 9], 0x0).unwrap();
+        assert_eq!(calls.borrow().len(), 4);
+    }
+
+    #[test]
+    fn watch_rebase() {
+        let mut memory = MemoryWatcher::new(PagedMemory::<4, 4>::new(0xab));
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let watch_calls = Rc::clone(&calls);
+        memory.watch(
+            0x2..=0x5,
+            Box::new(move |addr, old_value, new_value| {
+                watch_calls.borrow_mut().push((addr, old_value, new_value))
+            }),
+        );
+        memory.add_transaction(0x3, vec![1, 2], 0x0).unwrap();
+        calls.borrow_mut().clear();
+
+        memory.rebase(0x00);
+        assert_eq!(*calls.borrow(), vec![(0x2, 0xab, 0x00), (0x5, 0xab, 0x00)]);
+        memory.rebase(0x00);
+        assert_eq!(calls.borrow().len(), 2);
+    }
+}
@@ -0,0 +1,101 @@
+use std::io;
+
+use crate::{Memory, MemoryError};
+
+/// [`io::Write`] adapter that writes to a memory at a cursor.
+///
+/// Every `write` call records one transaction at the cursor and advances it. Writes are
+/// truncated at the end of the address space.
+#[derive(Debug)]
+pub struct MemoryWriter<'a, M: Memory> {
+    memory: &'a mut M,
+    cursor: usize,
+}
+
+impl<'a, M: Memory> MemoryWriter<'a, M> {
+    pub fn new(memory: &'a mut M, addr: usize) -> Result<Self, MemoryError> {
+        let mut writer = Self { memory, cursor: 0 };
+        writer.seek(addr)?;
+        Ok(writer)
+    }
+
+    /// Moves the cursor. `addr` may equal the address space size, after which writes accept
+    /// no more bytes.
+    pub fn seek(&mut self, addr: usize) -> Result<(), MemoryError> {
+        if addr > self.memory.address_space_size() {
+            return Err(MemoryError::AddressOutOfRange);
+        }
+        self.cursor = addr;
+        Ok(())
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn memory(&self) -> &M {
+        self.memory
+    }
+}
+
+impl<M: Memory> io::Write for MemoryWriter<'_, M> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf
+            .len()
+            .min(self.memory.address_space_size() - self.cursor);
+        if len == 0 {
+            return Ok(0);
+        }
+        self.memory
+            .add_transaction(self.cursor, buf[..len].to_vec(), 0x0)
+            .map_err(io::Error::other)?;
+        self.cursor += len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PagedMemory;
+    use io::Write;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn write() {
+        let mut memory = PagedMemory::<4, 4>::new(0xab);
+        let mut writer = MemoryWriter::new(&mut memory, 0x3).unwrap();
+        let copied = io::copy(&mut &[1, 2, 3, 4, 5, 6][..], &mut writer).unwrap();
+        assert_eq!(copied, 6);
+        assert_eq!(writer.cursor(), 0x9);
+        writer.write_all(&[7, 8]).unwrap();
+        assert_eq!(writer.memory().current_transaction_id(), 2);
+
+        writer.seek(0xe).unwrap();
+        assert_eq!(writer.write(&[9, 10, 11]).unwrap(), 2);
+        assert_eq!(writer.write(&[12]).unwrap(), 0);
+        assert_eq!(
+            writer.write_all(&[12]).unwrap_err().kind(),
+            io::ErrorKind::WriteZero
+        );
+        assert_eq!(writer.seek(0x11), Err(MemoryError::AddressOutOfRange));
+        assert_eq!(
+            memory.read(0x2, 14),
+            vec![0xab, 1, 2, 3, 4, 5, 6, 7, 8, 0xab, 0xab, 0xab, 9, 10]
+        );
+        assert!(MemoryWriter::new(&mut memory, 0x11).is_err());
+
+        memory.previous_transaction().unwrap();
+        let mut writer = MemoryWriter::new(&mut memory, 0x0).unwrap();
+        let error = writer.write(&[1]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Other);
+        assert_eq!(
+            error.into_inner().unwrap().downcast_ref::<MemoryError>(),
+            Some(&MemoryError::NotAtLatestTransaction)
+        );
+    }
+}